@@ -0,0 +1,143 @@
+use std::ops::Range;
+
+/// What a stretch of rendered text represents, which decides how it is styled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnnotationType {
+    Match,
+    SelectedMatch,
+    Keyword,
+    Type,
+    Function,
+    Macro,
+    Lifetime,
+    Attribute,
+    Number,
+    String,
+    Character,
+    Comment,
+    Constant,
+    Heading,
+    Emphasis,
+    Code,
+    Link,
+    Key,
+    Section,
+    Selection,
+    // A glyph standing in for a grapheme that cannot be shown as is.
+    Replacement,
+    LineNumber,
+    CursorLineNumber,
+}
+
+impl AnnotationType {
+/// The theme scope that styles this kind of text.
+    pub const fn scope(self) -> &'static str {
+        match self {
+            Self::Match => "ui.match",
+            Self::SelectedMatch => "ui.match.selected",
+            Self::Keyword => "keyword",
+            Self::Type => "type",
+            Self::Function => "function",
+            Self::Macro => "function.macro",
+            Self::Lifetime => "label",
+            Self::Attribute => "attribute",
+            Self::Number => "constant.numeric",
+            Self::String => "string",
+            Self::Character => "constant.character",
+            Self::Comment => "comment",
+            Self::Constant => "constant",
+            Self::Heading => "markup.heading",
+            Self::Emphasis => "markup.italic",
+            Self::Code => "markup.raw",
+            Self::Link => "markup.link",
+            Self::Key => "property",
+            Self::Section => "namespace",
+            Self::Selection => "ui.selection",
+            Self::Replacement => "ui.replacement",
+            Self::LineNumber => "ui.gutter",
+            Self::CursorLineNumber => "ui.gutter.selected",
+        }
+    }
+}
+
+/// Marks the graphemes `start..end` of a line.
+#[derive(Clone, Copy, Debug)]
+pub struct Annotation {
+    pub annotation_type: AnnotationType,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A rendered string together with byte ranges that should be styled.
+///
+/// Later annotations take precedence where ranges overlap.
+#[derive(Default)]
+pub struct AnnotatedString {
+    string: String,
+    annotations: Vec<(AnnotationType, Range<usize>)>,
+}
+
+/// A run of an `AnnotatedString` that is styled the same throughout.
+pub struct AnnotatedStringPart<'a> {
+    pub string: &'a str,
+    pub annotation_type: Option<AnnotationType>,
+}
+
+impl AnnotatedString {
+    pub fn push_str(&mut self, string: &str) {
+        self.string.push_str(string);
+    }
+
+    pub fn push(&mut self, character: char) {
+        self.string.push(character);
+    }
+
+    pub fn len(&self) -> usize {
+        self.string.len()
+    }
+
+/// Puts `prefix` in front of the string, annotated as a whole with `annotation_type`.
+    pub fn prepend(&mut self, prefix: &str, annotation_type: AnnotationType) {
+        if prefix.is_empty() {
+            return ;
+        }
+        self.string.insert_str(0, prefix);
+        for (_, bytes) in &mut self.annotations {
+            *bytes = bytes.start.saturating_add(prefix.len())..bytes.end.saturating_add(prefix.len());
+        }
+        self.annotations.insert(0, (annotation_type, 0..prefix.len()));
+    }
+
+    pub fn add_annotation(&mut self, annotation_type: AnnotationType, bytes: Range<usize>) {
+        if bytes.start < bytes.end {
+            self.annotations.push((annotation_type, bytes));
+        }
+    }
+
+/// Splits the string into consecutive parts at every annotation boundary.
+    pub fn parts(&self) -> Vec<AnnotatedStringPart<'_>> {
+        let mut boundaries: Vec<usize> = self
+            .annotations
+            .iter()
+            .flat_map(|(_, bytes)| [bytes.start, bytes.end])
+            .chain([0, self.string.len()])
+            .filter(|boundary| *boundary <= self.string.len())
+            .collect();
+        boundaries.sort_unstable();
+        boundaries.dedup();
+
+        boundaries
+            .windows(2)
+            .map(|window| {
+                let (start, end) = (window[0], window[1]);
+                let annotation_type = self
+                    .annotations
+                    .iter()
+                    .rev()
+                    .find(|(_, bytes)| bytes.start <= start && end <= bytes.end)
+                    .map(|(annotation_type, _)| *annotation_type);
+                AnnotatedStringPart { string: &self.string[start..end], annotation_type }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,212 @@
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Numbers the temporary files, so saves in one process never share one and
+// a file left behind by a crashed run is skipped rather than reused.
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+// How many temporary file names to try before giving up.
+const MAX_TEMP_ATTEMPTS: usize = 100;
+
+/// Crash-safe file replacement.
+///
+/// The new contents are written to a temporary file next to the target,
+/// flushed to disk and then renamed over the target, so the original file is
+/// either left untouched or fully replaced. Symlinks are followed, and the
+/// target's permissions (and on Unix its owner, when allowed) are kept.
+pub struct AtomicWrite;
+
+impl AtomicWrite {
+    pub fn write<F>(file_name: &str, write_contents: F) -> Result<(), Box<dyn Error>>
+    where
+        F: FnOnce(&mut dyn Write) -> Result<(), Box<dyn Error>>,
+    {
+        // Resolve symlinks so that the link keeps pointing at the file we replace.
+        let target = fs::canonicalize(file_name).unwrap_or_else(|_| PathBuf::from(file_name));
+        let metadata = fs::metadata(&target).ok();
+        let (temp_path, file) = Self::create_temp(&target, metadata.as_ref())?;
+
+        let result = Self::write_temp(file, &temp_path, metadata.as_ref(), write_contents)
+            .and_then(|()| fs::rename(&temp_path, &target).map_err(Into::into));
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        result?;
+
+        Self::sync_directory(&target);
+        Ok(())
+    }
+
+    fn temp_path_for(target: &Path, number: usize) -> Result<PathBuf, Box<dyn Error>> {
+        let file_name = target
+            .file_name()
+            .ok_or_else(|| format!("{} is not a file", target.display()))?;
+        let temp_name = format!(
+            ".{}.zq-{}-{number}.tmp",
+            file_name.to_string_lossy(),
+            std::process::id(),
+        );
+        Ok(target.with_file_name(temp_name))
+    }
+
+    // Creates the temporary copy under the first free name. It takes the
+    // target's permissions before any of the contents are written, so a
+    // private file is never readable by others.
+    fn create_temp(target: &Path, metadata: Option<&fs::Metadata>) -> Result<(PathBuf, File), Box<dyn Error>> {
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        if let Some(metadata) = metadata {
+            Self::set_mode(&mut options, metadata);
+        }
+        for _ in 0..MAX_TEMP_ATTEMPTS {
+            let temp_path = Self::temp_path_for(target, TEMP_COUNTER.fetch_add(1, Ordering::Relaxed))?;
+            match options.open(&temp_path) {
+                Ok(file) => return Ok((temp_path, file)),
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Err(format!("could not create a temporary file next to {}", target.display()).into())
+    }
+
+    fn write_temp<F>(
+        file: File,
+        temp_path: &Path,
+        metadata: Option<&fs::Metadata>,
+        write_contents: F,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        F: FnOnce(&mut dyn Write) -> Result<(), Box<dyn Error>>,
+    {
+        if let Some(metadata) = metadata {
+            // The mode given at creation is narrowed by the umask.
+            file.set_permissions(metadata.permissions())?;
+            Self::copy_owner(metadata, temp_path);
+        }
+
+        let mut writer = BufWriter::new(file);
+        write_contents(&mut writer)?;
+        let file = writer.into_inner().map_err(|err| err.into_error())?;
+        file.sync_all()?;
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn set_mode(options: &mut OpenOptions, metadata: &fs::Metadata) {
+        use std::os::unix::fs::{ OpenOptionsExt, PermissionsExt };
+        options.mode(metadata.permissions().mode());
+    }
+
+    #[cfg(not(unix))]
+    fn set_mode(_options: &mut OpenOptions, _metadata: &fs::Metadata) {}
+
+    #[cfg(unix)]
+    fn copy_owner(metadata: &fs::Metadata, temp_path: &Path) {
+        use std::os::unix::fs::MetadataExt;
+        // Only root may give files away, so failing here is expected and harmless.
+        let _ = std::os::unix::fs::chown(temp_path, Some(metadata.uid()), Some(metadata.gid()));
+    }
+
+    #[cfg(not(unix))]
+    fn copy_owner(_metadata: &fs::Metadata, _temp_path: &Path) {}
+
+    // Makes the rename itself durable. Not every platform can open directories,
+    // so this is best effort.
+    fn sync_directory(target: &Path) {
+        if let Some(dir) = target.parent() {
+            let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+            if let Ok(dir) = File::open(dir) {
+                let _ = dir.sync_all();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A file in the temporary directory, named after the test.
+    fn temp_target(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("zen_quill_atomic_{}_{name}.txt", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    // The temporary copies next to `path`.
+    fn temp_files(path: &Path) -> Vec<PathBuf> {
+        let prefix = format!(".{}.zq-", path.file_name().unwrap().to_string_lossy());
+        fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|temp_path| temp_path.file_name().unwrap().to_string_lossy().starts_with(&prefix))
+            .collect()
+    }
+
+    fn write_text(path: &Path, text: &str) -> Result<(), Box<dyn Error>> {
+        AtomicWrite::write(&path.to_string_lossy(), |writer| Ok(writer.write_all(text.as_bytes())?))
+    }
+
+    #[test]
+    fn replaces_the_contents() {
+        let path = temp_target("replace", "old");
+        write_text(&path, "new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn a_failed_write_leaves_the_file_and_no_copy() {
+        let path = temp_target("fail", "old");
+        let result = AtomicWrite::write(&path.to_string_lossy(), |writer| {
+            writer.write_all(b"partial")?;
+            Err("stop".into())
+        });
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        assert!(temp_files(&path).is_empty());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn a_stale_copy_from_a_crashed_run_is_skipped() {
+        let path = temp_target("stale", "old");
+        let number = TEMP_COUNTER.load(Ordering::Relaxed);
+        let stale: Vec<PathBuf> = (number..number.saturating_add(3))
+            .map(|number| AtomicWrite::temp_path_for(&path, number).unwrap())
+            .collect();
+        for stale_path in &stale {
+            fs::write(stale_path, "stale").unwrap();
+        }
+        write_text(&path, "new").unwrap();
+        write_text(&path, "newer").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "newer");
+        for stale_path in stale {
+            assert_eq!(fs::read_to_string(&stale_path).unwrap(), "stale");
+            fs::remove_file(stale_path).unwrap();
+        }
+        assert!(temp_files(&path).is_empty());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn the_copy_is_private_before_anything_is_written() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = temp_target("private", "secret");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        AtomicWrite::write(&path.to_string_lossy(), |writer| {
+            let [temp_path] = temp_files(&path).try_into().unwrap();
+            let mode = fs::metadata(&temp_path)?.permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+            Ok(writer.write_all(b"still secret")?)
+        })
+        .unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,43 @@
+use std::error::Error;
+use std::time::Duration;
+
+use crossterm::event::Event;
+
+use crate::core::frame::Frame;
+use crate::core::terminal::{ Position, Size };
+
+/// Where the editor draws its frames and reads its input from.
+///
+/// `Terminal` is the real terminal. `TestBackend` keeps the screen in
+/// memory and replays scripted events, so that whole editing sessions can
+/// run without one.
+pub trait Backend {
+/// Prepares for drawing, e.g. by switching the terminal to raw mode.
+    fn initialize(&mut self) -> Result<(), Box<dyn Error>>;
+
+/// Undoes `initialize`.
+    fn terminate(&mut self) -> Result<(), Box<dyn Error>>;
+
+    fn size(&self) -> Result<Size, Box<dyn Error>>;
+
+/// Shows `frame` with the cursor at `cursor`, or hidden if `None`.
+/// `previous` is the frame shown so far, so only what changed has to be
+/// redrawn; `None` if that is unknown.
+    fn draw(&mut self, frame: &Frame, previous: Option<&Frame>, cursor: Option<Position>) -> Result<(), Box<dyn Error>>;
+
+/// Writes a line of text outside of any frame, once terminated.
+    fn print_line(&mut self, text: &str) -> Result<(), Box<dyn Error>>;
+
+/// Puts `text` on the system clipboard, if the backend can reach it.
+    fn set_clipboard(&mut self, _text: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+/// Waits up to `timeout` for the next input event.
+    fn poll_event(&mut self, timeout: Duration) -> Result<Option<Event>, Box<dyn Error>>;
+
+/// Whether no more input will ever come, after which `Editor::run` returns.
+    fn is_input_closed(&self) -> bool {
+        false
+    }
+}
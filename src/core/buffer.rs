@@ -1,78 +1,794 @@
 use std::error::Error;
-use std::fs::read_to_string;
-use std::fs::File;
+use std::fs::read;
 use std::io::Write;
+use std::ops::Range;
+use std::path::{ Path, PathBuf };
+
+use ropey::Rope;
+use unicode_segmentation::UnicodeSegmentation;
 
 use crate::core::line::Line;
+use crate::core::history::{Edit, History};
+use crate::core::undo_file::UndoFile;
+use crate::core::line_ending::LineEnding;
+use crate::core::encoding::Encoding;
+use crate::core::atomic_write::AtomicWrite;
+use crate::core::pattern::Pattern;
+use crate::core::file_type::FileType;
+use crate::core::highlighter::Highlighter;
+#[cfg(feature = "tree-sitter")]
+use crate::core::syntax_tree::SyntaxTree;
+use crate::core::annotated_string::Annotation;
+use crate::core::Location;
 
 /// buffer
-#[derive(Default)]
+///
+/// The document is kept in a rope, so inserting or removing text costs
+/// O(log n) regardless of the file size. Lines are separated by `\n` inside
+/// the rope; `Line`s are only built on demand for the rows `View` asks for.
+///
+/// The file's encoding, its line ending and whether it ended with a line
+/// break are remembered separately, so saving writes them back unchanged.
 pub struct Buffer {
-    pub lines: Vec<Line>,
+    text: Rope,
     file_name: Option<String>,
+    history: History,
+    // Where the undo history is persisted; None keeps it in memory only.
+    undo_dir: Option<PathBuf>,
+    line_ending: LineEnding,
+    // The style of every line break, the final one included, while the
+    // file mixes LF and CRLF. Line breaks typed, pasted or brought back by
+    // undo take the style of the line they split.
+    mixed_line_endings: Option<Vec<LineEnding>>,
+    final_newline: bool,
+    encoding: Encoding,
+    // Whether the text or its on-disk format changed since the last load or save.
+    modified: bool,
+    highlighter: Highlighter,
+    #[cfg(feature = "tree-sitter")]
+    syntax_tree: Option<SyntaxTree>,
+}
+
+impl Default for Buffer {
+    fn default() -> Self {
+        Self {
+            text: Rope::default(),
+            file_name: None,
+            history: History::default(),
+            undo_dir: UndoFile::default_dir(),
+            line_ending: LineEnding::default(),
+            mixed_line_endings: None,
+            final_newline: true,
+            encoding: Encoding::default(),
+            modified: false,
+            highlighter: Highlighter::default(),
+            #[cfg(feature = "tree-sitter")]
+            syntax_tree: None,
+        }
+    }
 }
 
 impl Buffer {
-    pub fn load(file_name: &str) -> Result<Self, Box<dyn Error>> {
-        let contents = read_to_string(file_name)?;
+/// Opens `file_name`, restoring its undo history from `undo_dir` if one
+/// was kept there.
+    pub fn load(file_name: &str, undo_dir: Option<PathBuf>) -> Result<Self, Box<dyn Error>> {
+        let bytes = read(file_name)?;
+        Ok(Self::decode(file_name, &bytes, Encoding::detect(&bytes), undo_dir))
+    }
+
+    pub fn undo_dir(&self) -> Option<&Path> {
+        self.undo_dir.as_deref()
+    }
+
+    pub fn set_undo_dir(&mut self, undo_dir: Option<PathBuf>) {
+        self.undo_dir = undo_dir;
+    }
+
+/// Reads the file again from disk, decoding it as `encoding`.
+///
+/// Unsaved changes would be lost, so a modified buffer is refused.
+    pub fn reload_with_encoding(&mut self, encoding: Encoding) -> Result<(), Box<dyn Error>> {
+        if self.modified {
+            return Err("save the changes before reopening with another encoding".into());
+        }
+        if let Some(file_name) = &self.file_name {
+            let bytes = read(file_name)?;
+            let encoding = encoding.matching_bom(&bytes);
+            *self = Self::decode(file_name, &bytes, encoding, self.undo_dir.take());
+        }
+        Ok(())
+    }
+
+    fn decode(file_name: &str, bytes: &[u8], encoding: Encoding, undo_dir: Option<PathBuf>) -> Self {
+        let contents = encoding.decode(bytes);
 
-        let mut lines = Vec::new();
-        for line in contents.lines() {
-            lines.push(Line::from(line));
+        let line_endings = LineEnding::of_each_line(&contents);
+        let line_ending = LineEnding::majority(&line_endings);
+        let mixed_line_endings = line_endings
+            .iter()
+            .any(|other| *other != line_ending)
+            .then_some(line_endings);
+        let mut contents = contents.replace("\r\n", "\n");
+        let final_newline = contents.ends_with('\n');
+        if final_newline {
+            contents.pop();
         }
 
-        Ok(Self { lines, file_name: Some(file_name.to_string()) })
+        let text = Rope::from_str(&contents);
+        let history = undo_dir
+            .as_deref()
+            .and_then(|dir| UndoFile::load(dir, file_name, &text))
+            .unwrap_or_default();
+
+        Self {
+            text,
+            file_name: Some(file_name.to_string()),
+            history,
+            undo_dir,
+            line_ending,
+            mixed_line_endings,
+            final_newline,
+            encoding,
+            modified: false,
+            highlighter: Highlighter::new(FileType::from_file_name(file_name)),
+            #[cfg(feature = "tree-sitter")]
+            syntax_tree: SyntaxTree::new(FileType::from_file_name(file_name)),
+        }
     }
 
     pub fn height(&self) -> usize {
-        self.lines.len()
+        if self.text.len_chars() == 0 {
+            0
+        } else {
+            self.text.len_lines()
+        }
+    }
+
+/// Returns the line at `line_index`, split into graphemes for rendering.
+    pub fn line(&self, line_index: usize) -> Option<Line> {
+        self.line_string(line_index).map(|line| Line::from(&line))
+    }
+
+    pub fn grapheme_count(&self, line_index: usize) -> usize {
+        self.line(line_index).map_or(0, |line| line.grapheme_count())
+    }
+
+    // Returns the contents of a line without its trailing line break.
+    fn line_string(&self, line_index: usize) -> Option<String> {
+        if line_index >= self.height() {
+            return None;
+        }
+        let mut line = self.text.line(line_index).to_string();
+        if line.ends_with('\n') {
+            line.pop();
+        }
+        Some(line)
+    }
+
+/// Converts a location into an absolute char index into the rope.
+/// Grapheme indices past the end of the line are clamped to the line end.
+    pub fn char_index(&self, location: Location) -> Option<usize> {
+        let line = self.line_string(location.line_index)?;
+        let line_start = self.text.line_to_char(location.line_index);
+        let offset: usize = line
+            .graphemes(true)
+            .take(location.grapheme_index)
+            .map(|grapheme| grapheme.chars().count())
+            .sum();
+        Some(line_start.saturating_add(offset))
+    }
+
+/// Converts an absolute char index back into a location.
+    pub fn location_of(&self, char_index: usize) -> Location {
+        let char_index = char_index.min(self.text.len_chars());
+        let line_index = self.text.char_to_line(char_index);
+        let line_start = self.text.line_to_char(line_index);
+        let grapheme_index = self
+            .text
+            .slice(line_start..char_index)
+            .to_string()
+            .graphemes(true)
+            .count();
+        Location { grapheme_index, line_index }
+    }
+
+    // Applies an edit to the rope without recording it.
+    fn apply(&mut self, edit: &Edit) {
+        self.modified = true;
+        let line_index = self.text.char_to_line(edit.at().min(self.text.len_chars()));
+        let (removed_lines, inserted_lines) = match edit {
+            Edit::Insert { text, .. } => (0, text.matches('\n').count()),
+            Edit::Delete { text, .. } => (text.matches('\n').count(), 0),
+        };
+        self.highlighter.edit(line_index, removed_lines, inserted_lines);
+        if let Some(line_endings) = &mut self.mixed_line_endings {
+            let split = line_endings.get(line_index).copied().unwrap_or(self.line_ending);
+            let removed = line_index.min(line_endings.len())..line_index.saturating_add(removed_lines).min(line_endings.len());
+            line_endings.splice(removed, std::iter::repeat_n(split, inserted_lines));
+        }
+        #[cfg(feature = "tree-sitter")]
+        if let Some(syntax_tree) = &mut self.syntax_tree {
+            syntax_tree.edit(&self.text, edit);
+        }
+        match edit {
+            Edit::Insert { at, text } => self.text.insert(*at, text),
+            Edit::Delete { at, text } => {
+                let end = at.saturating_add(text.chars().count());
+                self.text.remove(*at..end);
+            }
+        }
+    }
+
+    // Inserts text at a char index and records it in the undo history.
+    fn insert_at(&mut self, at: usize, text: &str, typing: bool) {
+        let edit = Edit::Insert { at, text: text.to_string() };
+        self.apply(&edit);
+        self.history.record(edit, typing);
+    }
+
+    // Removes `len` chars starting at a char index and records it in the undo history.
+    fn remove_at(&mut self, at: usize, len: usize) {
+        let text = self.text.slice(at..at.saturating_add(len)).to_string();
+        let edit = Edit::Delete { at, text };
+        self.apply(&edit);
+        self.history.record(edit, false);
+    }
+
+    pub fn insert_char(&mut self, character: char, location: Location) {
+        let typing = character != '\n';
+        self.insert_at_location(&character.to_string(), location, typing);
     }
 
-    pub fn insert_char(&mut self, character: char, location: super::view::Location) {
-        if location.line_index > self.lines.len() {
+/// Inserts `text`, which may span several lines, as a single edit and
+/// returns the location right after it.
+    pub fn insert_text(&mut self, text: &str, location: Location) -> Location {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        if text.is_empty() {
+            return location;
+        }
+        self.insert_at_location(&text, location, false)
+            .map_or(location, |end| self.location_of(end))
+    }
+
+    // Inserts text at a location, where the line past the last one is
+    // started by the insert. Returns the char index right after the text.
+    fn insert_at_location(&mut self, text: &str, location: Location, typing: bool) -> Option<usize> {
+        let height = self.height();
+        if location.line_index > height {
+            return None;
+        }
+        let (at, text) = if location.line_index == height {
+            let text = if height > 0 { format!("\n{text}") } else { text.to_string() };
+            (self.text.len_chars(), text)
+        } else {
+            (self.char_index(location)?, text.to_string())
+        };
+        self.insert_at(at, &text, typing);
+        Some(at.saturating_add(text.chars().count()))
+    }
+
+    pub fn delete_char(&mut self, location: Location) {
+        let Some(line) = self.line_string(location.line_index) else {
+            return ;
+        };
+        let Some(start) = self.char_index(location) else {
+            return ;
+        };
+        let grapheme_count = line.graphemes(true).count();
+
+        if location.grapheme_index >= grapheme_count &&
+           self.height() > location.line_index.saturating_add(1) {
+
+            // Joins the next line by removing the line break.
+            self.remove_at(start, 1);
+
+        } else if location.grapheme_index < grapheme_count {
+
+            let grapheme_len = line
+                .graphemes(true)
+                .nth(location.grapheme_index)
+                .map_or(0, |grapheme| grapheme.chars().count());
+            self.remove_at(start, grapheme_len);
+
+        }
+    }
+
+    pub fn insert_newline(&mut self, location: Location) {
+        let height = self.height();
+        if location.line_index == height {
+            self.insert_at(self.text.len_chars(), "\n", false);
             return ;
         }
-        if location.line_index == self.lines.len() {
-            self.lines.push(Line::from(&character.to_string()));
-        } else if let Some(line) = self.lines.get_mut(location.line_index) {
-            line.insert_char(character, location.grapheme_index);
+        if let Some(char_index) = self.char_index(location) {
+            self.insert_at(char_index, "\n", false);
         }
     }
 
-    pub fn delete_char(&mut self, location: super::view::Location) {
-        if let Some(line) = self.lines.get(location.line_index) {
-            if location.grapheme_index >= line.grapheme_count() && 
-               self.lines.len() > location.line_index.saturating_add(1) {
+/// Removes the text between two locations, given in either order, as a
+/// single undo step. A location past the last line stands for the end of
+/// the buffer.
+    pub fn delete_range(&mut self, from: Location, to: Location) {
+        let end_of_text = self.text.len_chars();
+        let from = self.char_index(from).unwrap_or(end_of_text);
+        let to = self.char_index(to).unwrap_or(end_of_text);
+        let (start, end) = (from.min(to), from.max(to));
+        if start < end {
+            self.remove_at(start, end.saturating_sub(start));
+        }
+    }
 
-                let next_line = self.lines.remove(location.line_index.saturating_add(1));
-                self.lines[location.line_index].append(&next_line);
+/// Replaces the text between two locations, given in either order, with
+/// `text` as a single undo step, and returns the location right after it.
+    pub fn replace_text(&mut self, from: Location, to: Location, text: &str) -> Location {
+        let end_of_text = self.text.len_chars();
+        let from_index = self.char_index(from).unwrap_or(end_of_text);
+        let to_index = self.char_index(to).unwrap_or(end_of_text);
+        let start = if from_index <= to_index { from } else { to };
+        self.history.begin_group();
+        self.delete_range(from, to);
+        let end = self.insert_text(text, start);
+        self.history.end_group();
+        end
+    }
 
-            } else if location.grapheme_index < line.grapheme_count() {
+/// Starts a group of edits that are undone together, until `end_group`.
+    pub fn begin_group(&mut self) {
+        self.history.begin_group();
+    }
 
-                self.lines[location.line_index].delete(location.grapheme_index);
-                
-            }
+    pub fn end_group(&mut self) {
+        self.history.end_group();
+    }
+
+/// Switches the file between LF and CRLF line endings; takes effect on the next save.
+///
+/// A file with mixed line endings first gets the style most of its lines use.
+    pub fn toggle_line_ending(&mut self) {
+        if self.mixed_line_endings.take().is_none() {
+            self.line_ending = self.line_ending.toggled();
         }
+        self.modified = true;
+    }
+
+    pub const fn line_ending(&self) -> LineEnding {
+        self.line_ending
     }
 
-    pub fn insert_newline(&mut self, location: super::view::Location) {
-        if location.line_index == self.lines.len() {
-            self.lines.push(Line::default());
+/// Whether the file mixes LF and CRLF; each line then keeps its own.
+    pub const fn has_mixed_line_endings(&self) -> bool {
+        self.mixed_line_endings.is_some()
+    }
+
+    pub const fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+/// Changes the encoding the file is written in on the next save.
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        if encoding != self.encoding {
+            self.encoding = encoding;
+            self.modified = true;
+        }
+    }
+
+    pub fn file_name(&self) -> Option<&str> {
+        self.file_name.as_deref()
+    }
+
+    pub fn file_type(&self) -> FileType {
+        self.file_name.as_deref().map_or(FileType::default(), FileType::from_file_name)
+    }
+
+/// Brings syntax highlighting up to date for `lines`.
+    pub fn highlight(&mut self, lines: Range<usize>) {
+        #[cfg(feature = "tree-sitter")]
+        if let Some(syntax_tree) = &mut self.syntax_tree {
+            syntax_tree.highlight(&self.text, lines);
             return ;
         }
-        if let Some(line) = self.lines.get_mut(location.line_index) {
-            let new_line = line.split(location.grapheme_index);
-            self.lines.insert(location.line_index.saturating_add(1), new_line);
+        self.highlighter.update(&self.text, lines.end);
+    }
+
+/// The syntax annotations of a line, as of the last `highlight`.
+    pub fn syntax_annotations(&self, line_index: usize) -> &[Annotation] {
+        #[cfg(feature = "tree-sitter")]
+        if let Some(syntax_tree) = &self.syntax_tree {
+            return syntax_tree.annotations(line_index);
         }
+        self.highlighter.annotations(line_index)
     }
 
-    pub fn save(&self) -> Result<(), Box<dyn Error>> {
-        if let Some(file_name) = &self.file_name {
-            let mut file = File::create(file_name)?;
-            for line in &self.lines {
-                writeln!(file, "{line}")?;
+/// Where the syntax node enclosing `location` starts.
+    #[cfg(feature = "tree-sitter")]
+    pub fn parent_node(&mut self, location: Location) -> Option<Location> {
+        let byte = self.text.char_to_byte(self.char_index(location)?);
+        let start = self.syntax_tree.as_mut()?.parent_node(&self.text, byte)?;
+        Some(self.location_of(self.text.byte_to_char(start)))
+    }
+
+/// The char range of the smallest syntax node that is larger than the
+/// char range `selected` and contains it.
+    #[cfg(feature = "tree-sitter")]
+    pub fn enclosing_node(&mut self, selected: Range<usize>) -> Option<Range<usize>> {
+        let bytes = self.text.char_to_byte(selected.start)..self.text.char_to_byte(selected.end);
+        let node = self.syntax_tree.as_mut()?.enclosing_node(&self.text, bytes)?;
+        Some(self.text.byte_to_char(node.start)..self.text.byte_to_char(node.end))
+    }
+
+/// Where the first function after `location` starts.
+    #[cfg(feature = "tree-sitter")]
+    pub fn next_function(&mut self, location: Location) -> Option<Location> {
+        let byte = self.text.char_to_byte(self.char_index(location)?);
+        let start = self.syntax_tree.as_mut()?.next_function(&self.text, byte)?;
+        Some(self.location_of(self.text.byte_to_char(start)))
+    }
+
+/// Whether the buffer has a syntax tree to navigate.
+    #[cfg(feature = "tree-sitter")]
+    pub const fn has_syntax_tree(&self) -> bool {
+        self.syntax_tree.is_some()
+    }
+
+    pub const fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+/// Ends the current run of typed characters, so the next edit gets its own undo step.
+    pub fn seal_history(&mut self) {
+        self.history.seal();
+    }
+
+/// Reverts the last transaction and returns where the cursor should go.
+    pub fn undo(&mut self) -> Option<Location> {
+        let transaction = self.history.undo()?;
+        for edit in transaction.edits.iter().rev() {
+            self.apply(&edit.inverse());
+        }
+        Some(self.location_of(transaction.undo_cursor()))
+    }
+
+/// Re-applies the last undone transaction and returns where the cursor should go.
+    pub fn redo(&mut self) -> Option<Location> {
+        let transaction = self.history.redo()?;
+        for edit in &transaction.edits {
+            self.apply(edit);
+        }
+        Some(self.location_of(transaction.redo_cursor()))
+    }
+
+/// Writes the buffer back to its file.
+///
+/// The file is replaced atomically, so a failed save leaves the previous
+/// contents intact.
+    pub fn save(&mut self) -> Result<(), Box<dyn Error>> {
+        let Some(file_name) = self.file_name.clone() else {
+            return Ok(());
+        };
+        AtomicWrite::write(&file_name, |writer| self.write_contents(writer))?;
+        self.modified = false;
+
+        // The file itself is safe at this point; losing the undo history
+        // is not worth failing the save over.
+        self.history.seal();
+        if let Some(dir) = &self.undo_dir {
+            let _ = UndoFile::save(dir, &file_name, &self.text, &self.history);
+        }
+        Ok(())
+    }
+
+/// Finds the next match of `pattern` at or after `from`, wrapping
+/// around the end of the buffer.
+    pub fn search_forward(&self, pattern: &Pattern, from: Location) -> Option<Location> {
+        let height = self.height();
+        if height == 0 {
+            return None;
+        }
+        let from_line = from.line_index.min(height.saturating_sub(1));
+        let lines = (from_line..height).chain(0..=from_line);
+        for (step, line_index) in lines.enumerate() {
+            let from_grapheme = if step == 0 { from.grapheme_index } else { 0 };
+            if let Some(grapheme_index) = self
+                .line(line_index)
+                .and_then(|line| line.search_forward(pattern, from_grapheme))
+            {
+                return Some(Location { grapheme_index, line_index });
+            }
+        }
+        None
+    }
+
+/// Finds the closest match of `pattern` that starts before `from`,
+/// wrapping around the start of the buffer.
+    pub fn search_backward(&self, pattern: &Pattern, from: Location) -> Option<Location> {
+        let height = self.height();
+        if height == 0 {
+            return None;
+        }
+        let from_line = from.line_index.min(height.saturating_sub(1));
+        let lines = (0..=from_line).rev().chain((from_line..height).rev());
+        for (step, line_index) in lines.enumerate() {
+            let before_grapheme = if step == 0 { from.grapheme_index } else { usize::MAX };
+            if let Some(grapheme_index) = self
+                .line(line_index)
+                .and_then(|line| line.search_backward(pattern, before_grapheme))
+            {
+                return Some(Location { grapheme_index, line_index });
+            }
+        }
+        None
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.text.len_chars()
+    }
+
+/// The text in the char range `range`.
+    pub fn text_in(&self, range: Range<usize>) -> String {
+        let end = range.end.min(self.text.len_chars());
+        self.text.slice(range.start.min(end)..end).to_string()
+    }
+
+/// Finds the first match of `pattern` that starts inside the char range
+/// `within` and returns its char range together with the text that would
+/// replace it.
+///
+/// Lines are matched one at a time, like `search_forward` does, so only
+/// the lines looked at are copied out of the rope.
+    pub fn find_replacement(
+        &self,
+        pattern: &Pattern,
+        replacement: &str,
+        within: Range<usize>,
+    ) -> Option<(Range<usize>, String)> {
+        if within.start > self.text.len_chars() {
+            return None;
+        }
+        let first_line = self.text.char_to_line(within.start);
+        (first_line..self.height())
+            .take_while(|line_index| self.text.line_to_char(*line_index) < within.end)
+            .find_map(|line_index| {
+                self.line_replacements(pattern, replacement, line_index)
+                    .into_iter()
+                    .find(|(range, _)| within.contains(&range.start))
+            })
+    }
+
+/// Replaces the chars in `range` with `text` as a single undo step.
+    pub fn replace_range(&mut self, range: Range<usize>, text: &str) {
+        self.history.begin_group();
+        if !range.is_empty() {
+            self.remove_at(range.start, range.len());
+        }
+        if !text.is_empty() {
+            self.insert_at(range.start, text, false);
+        }
+        self.history.end_group();
+    }
+
+/// Replaces every match of `pattern` that starts inside one of the char
+/// ranges in `within`, as a single undo step. Returns how many were replaced.
+    pub fn replace_all(&mut self, pattern: &Pattern, replacement: &str, within: &[Range<usize>]) -> usize {
+        let len_chars = self.text.len_chars();
+        let (Some(start), Some(end)) = (
+            within.iter().map(|range| range.start.min(len_chars)).min(),
+            within.iter().map(|range| range.end.min(len_chars)).max(),
+        ) else {
+            return 0;
+        };
+        let lines = self.text.char_to_line(start)..=self.text.char_to_line(end);
+        let replacements: Vec<(Range<usize>, String)> = lines
+            .flat_map(|line_index| self.line_replacements(pattern, replacement, line_index))
+            .filter(|(range, _)| within.iter().any(|within| within.contains(&range.start)))
+            .collect();
+
+        // Back to front, so earlier char indices stay valid.
+        self.history.begin_group();
+        for (range, text) in replacements.iter().rev() {
+            self.replace_range(range.clone(), text);
+        }
+        self.history.end_group();
+        replacements.len()
+    }
+
+    // The matches of `pattern` on one line, as char ranges into the rope,
+    // each with the text that would replace it.
+    fn line_replacements(&self, pattern: &Pattern, replacement: &str, line_index: usize) -> Vec<(Range<usize>, String)> {
+        let Some(line) = self.line_string(line_index) else {
+            return Vec::new();
+        };
+        let line_start = self.text.line_to_char(line_index);
+        let char_index = |byte_index: usize| line_start.saturating_add(line[..byte_index].chars().count());
+        pattern
+            .find_all(&line)
+            .into_iter()
+            .map(|found| {
+                let range = char_index(found.start)..char_index(found.end);
+                (range, pattern.expand(&line, &found, replacement))
+            })
+            .collect()
+    }
+
+/// Saves the buffer under a new file name, which it keeps from then on.
+    pub fn save_as(&mut self, file_name: &str) -> Result<(), Box<dyn Error>> {
+        let previous = self.file_name.replace(file_name.to_string());
+        let result = self.save();
+        if result.is_err() {
+            self.file_name = previous;
+        } else if self.file_type() != self.highlighter.file_type() {
+            self.highlighter = Highlighter::new(self.file_type());
+            #[cfg(feature = "tree-sitter")]
+            {
+                self.syntax_tree = SyntaxTree::new(self.file_type());
+            }
+        }
+        result
+    }
+
+    fn write_contents(&self, writer: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+        let line_ending = self.line_ending.as_str();
+        writer.write_all(self.encoding.bom())?;
+        if let Some(line_endings) = &self.mixed_line_endings {
+            return self.write_lines(writer, line_endings);
+        }
+        for chunk in self.text.chunks() {
+            let chunk = if self.line_ending == LineEnding::Lf {
+                self.encoding.encode(chunk)?
+            } else {
+                self.encoding.encode(&chunk.replace('\n', line_ending))?
+            };
+            writer.write_all(&chunk)?;
+        }
+        if self.final_newline {
+            writer.write_all(&self.encoding.encode(line_ending)?)?;
+        }
+        Ok(())
+    }
+
+    // Writes the text line by line, ending each with its own line break.
+    fn write_lines(&self, writer: &mut dyn Write, line_endings: &[LineEnding]) -> Result<(), Box<dyn Error>> {
+        let last_line = self.text.len_lines().saturating_sub(1);
+        for (line_index, line) in self.text.lines().enumerate() {
+            let line = line.to_string();
+            let content = line.strip_suffix('\n');
+            writer.write_all(&self.encoding.encode(content.unwrap_or(&line))?)?;
+            if content.is_some() || (line_index == last_line && self.final_newline) {
+                let line_ending = line_endings.get(line_index).copied().unwrap_or(self.line_ending);
+                writer.write_all(&self.encoding.encode(line_ending.as_str())?)?;
             }
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(contents: &str) -> Buffer {
+        Buffer::decode("test.txt", contents.as_bytes(), Encoding::Utf8, None)
+    }
+
+    fn written(buffer: &Buffer) -> String {
+        let mut bytes = Vec::new();
+        buffer.write_contents(&mut bytes).unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    fn line(buffer: &Buffer, line_index: usize) -> Option<String> {
+        buffer.line(line_index).map(|line| line.to_string())
+    }
+
+    #[test]
+    fn lines_are_split_at_line_breaks() {
+        let buffer = decode("one\ntwo\n");
+        assert_eq!(buffer.height(), 2);
+        assert_eq!(line(&buffer, 1).as_deref(), Some("two"));
+        assert_eq!(line(&buffer, 2), None);
+        assert_eq!(decode("").height(), 0);
+        assert_eq!(written(&decode("\n")), "\n");
+    }
+
+    #[test]
+    fn only_newlines_break_lines() {
+        let buffer = decode("a\x0cb\nc\u{2028}d\u{85}e\rf\n");
+        assert_eq!(buffer.height(), 2);
+        assert_eq!(line(&buffer, 0).as_deref(), Some("a\x0cb"));
+        assert_eq!(line(&buffer, 1).as_deref(), Some("c\u{2028}d\u{85}e\rf"));
+        assert_eq!(buffer.char_index(Location { line_index: 1, grapheme_index: 0 }), Some(4));
+    }
+
+    #[test]
+    fn locations_count_graphemes() {
+        let buffer = decode("e\u{301}x\n日本\n");
+        assert_eq!(buffer.grapheme_count(0), 2);
+        assert_eq!(buffer.char_index(Location { line_index: 0, grapheme_index: 1 }), Some(2));
+        assert_eq!(buffer.char_index(Location { line_index: 0, grapheme_index: 9 }), Some(3));
+        assert_eq!(buffer.char_index(Location { line_index: 1, grapheme_index: 1 }), Some(5));
+        assert_eq!(buffer.char_index(Location { line_index: 2, grapheme_index: 0 }), None);
+        let location = buffer.location_of(5);
+        assert_eq!((location.line_index, location.grapheme_index), (1, 1));
+        let location = buffer.location_of(99);
+        assert_eq!((location.line_index, location.grapheme_index), (1, 2));
+    }
+
+    #[test]
+    fn edits_change_the_text_in_place() {
+        let mut buffer = decode("ab\ncd\n");
+        buffer.insert_char('x', Location { line_index: 0, grapheme_index: 1 });
+        buffer.insert_newline(Location { line_index: 1, grapheme_index: 1 });
+        assert_eq!(written(&buffer), "axb\nc\nd\n");
+        buffer.delete_char(Location { line_index: 0, grapheme_index: 3 });
+        buffer.delete_char(Location { line_index: 0, grapheme_index: 0 });
+        assert_eq!(written(&buffer), "xbc\nd\n");
+        buffer.insert_char('!', Location { line_index: 2, grapheme_index: 0 });
+        assert_eq!(written(&buffer), "xbc\nd\n!\n");
+        assert!(buffer.is_modified());
+    }
+
+    #[test]
+    fn a_missing_final_newline_stays_missing() {
+        let mut buffer = decode("a\r\nb");
+        buffer.insert_char('c', Location { line_index: 1, grapheme_index: 1 });
+        assert_eq!(written(&buffer), "a\r\nbc");
+    }
+
+    #[test]
+    fn replace_matches_line_by_line_like_search() {
+        let mut buffer = decode("xa xb\nxc\n");
+        let pattern = Pattern::new("^x(.)", true).unwrap();
+        assert_eq!(buffer.find_replacement(&pattern, "$1", 1..9), Some((6..8, "c".to_string())));
+        let everything: Vec<Range<usize>> = std::iter::once(0..buffer.len_chars()).collect();
+        assert_eq!(buffer.replace_all(&pattern, "<$1>", &everything), 2);
+        assert_eq!(written(&buffer), "<a> xb\n<c>\n");
+        buffer.undo();
+        assert_eq!(written(&buffer), "xa xb\nxc\n");
+    }
+
+    #[test]
+    fn replace_all_keeps_to_the_given_ranges() {
+        let mut buffer = decode("one one\none\n");
+        let pattern = Pattern::new("one", false).unwrap();
+        assert_eq!(buffer.replace_all(&pattern, "1", &[2..6, 8..11]), 2);
+        assert_eq!(written(&buffer), "one 1\n1\n");
+        assert_eq!(buffer.find_replacement(&pattern, "1", 1..6), None);
+    }
+
+    #[test]
+    fn untouched_lines_keep_mixed_line_endings() {
+        let buffer = decode("a\r\nb\nc\r\n");
+        assert!(buffer.has_mixed_line_endings());
+        assert_eq!(buffer.line_ending(), LineEnding::CrLf);
+        assert_eq!(written(&buffer), "a\r\nb\nc\r\n");
+    }
+
+    #[test]
+    fn mixed_line_endings_follow_newlines_past_other_breaks() {
+        let contents = "a\x0cb\r\nc\u{2028}\nd\r\n";
+        let mut buffer = decode(contents);
+        assert_eq!(written(&buffer), contents);
+        buffer.insert_char('x', Location { line_index: 1, grapheme_index: 0 });
+        assert_eq!(written(&buffer), "a\x0cb\r\nxc\u{2028}\nd\r\n");
+    }
+
+    #[test]
+    fn new_line_breaks_take_the_style_of_the_line_they_split() {
+        let mut buffer = decode("a\r\nb\nc\r\n");
+        buffer.insert_text("x\ny", Location { line_index: 1, grapheme_index: 1 });
+        buffer.insert_newline(Location { line_index: 0, grapheme_index: 1 });
+        assert_eq!(written(&buffer), "a\r\n\r\nbx\ny\nc\r\n");
+        while buffer.undo().is_some() {}
+        assert_eq!(written(&buffer), "a\r\nb\nc\r\n");
+        buffer.delete_range(Location { line_index: 0, grapheme_index: 1 }, Location { line_index: 1, grapheme_index: 1 });
+        assert_eq!(written(&buffer), "a\nc\r\n");
+    }
+
+    #[test]
+    fn toggling_mixed_line_endings_settles_on_the_majority() {
+        let mut buffer = decode("a\r\nb\nc\r\n");
+        buffer.toggle_line_ending();
+        assert!(!buffer.has_mixed_line_endings());
+        assert_eq!(written(&buffer), "a\r\nb\r\nc\r\n");
+        buffer.toggle_line_ending();
+        assert_eq!(written(&buffer), "a\nb\nc\n");
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::Write;
+use std::process::{ Command, Stdio };
+
+/// The register cut, copy and paste use unless another one is picked. It
+/// is the one kept in sync with the system clipboard.
+pub const DEFAULT_REGISTER: char = '"';
+
+/// A program that reaches the system clipboard without going through the
+/// terminal, for terminals that don't support OSC 52.
+#[derive(Clone, Copy)]
+pub enum ClipboardCommand {
+    Xclip,
+    WlClipboard,
+}
+
+impl ClipboardCommand {
+/// The command named on the command line, e.g. `xclip` or `wl-copy`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "xclip" => Some(Self::Xclip),
+            "wl-copy" | "wl-clipboard" => Some(Self::WlClipboard),
+            _ => None,
+        }
+    }
+
+    const fn copy_command(self) -> (&'static str, &'static [&'static str]) {
+        match self {
+            Self::Xclip => ("xclip", &["-selection", "clipboard", "-in"]),
+            Self::WlClipboard => ("wl-copy", &[]),
+        }
+    }
+
+    const fn paste_command(self) -> (&'static str, &'static [&'static str]) {
+        match self {
+            Self::Xclip => ("xclip", &["-selection", "clipboard", "-out"]),
+            Self::WlClipboard => ("wl-paste", &["--no-newline"]),
+        }
+    }
+
+    fn copy(self, text: &str) -> Result<(), Box<dyn Error>> {
+        let (program, args) = self.copy_command();
+        // Both fork to serve the clipboard, so their output must not keep
+        // the terminal attached.
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| format!("{program}: {err}"))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        if !child.wait()?.success() {
+            return Err(format!("{program} failed").into());
+        }
+        Ok(())
+    }
+
+    fn paste(self) -> Result<String, Box<dyn Error>> {
+        let (program, args) = self.paste_command();
+        let output = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .map_err(|err| format!("{program}: {err}"))?;
+        if !output.status.success() {
+            return Err(format!("{program} failed").into());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// Text kept for pasting, in registers named by a character.
+///
+/// What goes into the default register also goes to the system clipboard:
+/// through the configured command if there is one, otherwise the editor
+/// sends it to the terminal as an OSC 52 sequence, which also works over
+/// SSH. Only a command can read the system clipboard back, so without one
+/// pasting uses what was copied in the editor.
+#[derive(Default)]
+pub struct Clipboard {
+    registers: HashMap<char, String>,
+    command: Option<ClipboardCommand>,
+}
+
+impl Clipboard {
+    pub const fn set_command(&mut self, command: ClipboardCommand) {
+        self.command = Some(command);
+    }
+
+/// Whether the system clipboard is reached through a command rather than
+/// OSC 52.
+    pub const fn has_command(&self) -> bool {
+        self.command.is_some()
+    }
+
+/// Puts `text` into `register`.
+    pub fn copy(&mut self, register: char, text: &str) -> Result<(), Box<dyn Error>> {
+        self.registers.insert(register, text.to_string());
+        match self.command {
+            Some(command) if register == DEFAULT_REGISTER => command.copy(text),
+            _ => Ok(()),
+        }
+    }
+
+/// The text in `register`, or None if nothing was put there yet.
+    pub fn paste(&self, register: char) -> Result<Option<String>, Box<dyn Error>> {
+        match self.command {
+            Some(command) if register == DEFAULT_REGISTER => command.paste().map(Some),
+            _ => Ok(self.registers.get(&register).cloned()),
+        }
+    }
+}
+
+/// The escape sequence that asks the terminal to put `text` on the system
+/// clipboard.
+pub fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", base64(text.as_bytes()))
+}
+
+// Standard base64 with padding, which OSC 52 expects.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3).saturating_mul(4));
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |group, (index, byte)| group | u32::from(*byte) << (16 - 8 * index));
+        for index in 0..4 {
+            if index <= chunk.len() {
+                let sextet = (group >> (18 - 6 * index)) & 0x3f;
+                encoded.push(char::from(ALPHABET[sextet as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_matches_the_rfc_4648_vectors() {
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (text, encoded) in vectors {
+            assert_eq!(base64(text.as_bytes()), encoded);
+        }
+    }
+
+    #[test]
+    fn osc52_wraps_the_encoded_text_in_the_escape_sequence() {
+        assert_eq!(osc52("hi"), "\x1b]52;c;aGk=\x07");
+        assert_eq!(osc52("é\n"), "\x1b]52;c;w6kK\x07");
+    }
+}
@@ -12,21 +12,57 @@ pub enum Direction {
     PageDown,
     Home,
     End,
+    WordLeft,
+    WordRight,
+    DocumentStart,
+    DocumentEnd,
 }
 
 #[derive(PartialEq)]
 pub enum EditorCommand {
     Move(Direction),
+    // Moves the cursor while extending the selection.
+    Select(Direction),
     Resize(Size),
     Quit,
     Insert(char),
     Backspace,
     Delete,
+    DeleteWordBackward,
+    DeleteWordForward,
     Tab,
     Enter,
     Save,
+    Undo,
+    Redo,
+    ToggleLineEnding,
+    CycleEncoding,
+    ReopenWithEncoding,
+    Dismiss,
+    GoToLine,
+    Search,
+    Replace,
+    ToggleRegex,
+    ProjectSearch,
+    CycleTheme,
+    CycleLineNumbers,
+    ToggleSoftWrap,
+    Cut,
+    Copy,
+    Paste,
+    // Picks the register the next cut, copy or paste uses.
+    SelectRegister,
+    #[cfg(feature = "tree-sitter")]
+    ParentNode,
+    #[cfg(feature = "tree-sitter")]
+    SelectNode,
+    #[cfg(feature = "tree-sitter")]
+    NextFunction,
 }
 
+// Ctrl+Shift, which selects while moving like Ctrl does.
+const SELECT_BY_WORD: KeyModifiers = KeyModifiers::CONTROL.union(KeyModifiers::SHIFT);
+
 impl TryFrom<&Event> for EditorCommand {
     type Error = String;
 
@@ -39,8 +75,31 @@ impl TryFrom<&Event> for EditorCommand {
                 .. 
             }) => {
                 match (code, *modifiers) {
-                    (KeyCode::Char('z'), KeyModifiers::CONTROL) => Ok(Self::Quit),
+                    (KeyCode::Char('q'), KeyModifiers::CONTROL) => Ok(Self::Quit),
                     (KeyCode::Char('s'), KeyModifiers::CONTROL) => Ok(Self::Save),
+                    (KeyCode::Char('z'), KeyModifiers::CONTROL) => Ok(Self::Undo),
+                    (KeyCode::Char('y'), KeyModifiers::CONTROL) => Ok(Self::Redo),
+                    (KeyCode::Char('g'), KeyModifiers::CONTROL) => Ok(Self::GoToLine),
+                    (KeyCode::Char('x'), KeyModifiers::CONTROL) => Ok(Self::Cut),
+                    (KeyCode::Char('c'), KeyModifiers::CONTROL) => Ok(Self::Copy),
+                    (KeyCode::Char('v'), KeyModifiers::CONTROL) => Ok(Self::Paste),
+                    (KeyCode::Char('f'), KeyModifiers::CONTROL) => Ok(Self::Search),
+                    (KeyCode::Char('r'), KeyModifiers::CONTROL) => Ok(Self::Replace),
+                    (KeyCode::Char('r'), KeyModifiers::ALT    ) => Ok(Self::ToggleRegex),
+                    (KeyCode::Char('f'), KeyModifiers::ALT    ) => Ok(Self::ProjectSearch),
+                    (KeyCode::Char('t'), KeyModifiers::ALT    ) => Ok(Self::CycleTheme),
+                    (KeyCode::Char('n'), KeyModifiers::ALT    ) => Ok(Self::CycleLineNumbers),
+                    (KeyCode::Char('w'), KeyModifiers::ALT    ) => Ok(Self::ToggleSoftWrap),
+                    (KeyCode::Char('l'), KeyModifiers::ALT    ) => Ok(Self::ToggleLineEnding),
+                    (KeyCode::Char('e'), KeyModifiers::ALT    ) => Ok(Self::CycleEncoding),
+                    (KeyCode::Char('o'), KeyModifiers::ALT    ) => Ok(Self::ReopenWithEncoding),
+                    (KeyCode::Char('\''), KeyModifiers::ALT   ) => Ok(Self::SelectRegister),
+                    #[cfg(feature = "tree-sitter")]
+                    (KeyCode::Up       , KeyModifiers::ALT    ) => Ok(Self::ParentNode),
+                    #[cfg(feature = "tree-sitter")]
+                    (KeyCode::Char('s'), KeyModifiers::ALT    ) => Ok(Self::SelectNode),
+                    #[cfg(feature = "tree-sitter")]
+                    (KeyCode::Down     , KeyModifiers::ALT    ) => Ok(Self::NextFunction),
 
                     (
                         KeyCode::Char(character), 
@@ -50,6 +109,7 @@ impl TryFrom<&Event> for EditorCommand {
 
                     (KeyCode::Tab     , KeyModifiers::NONE) => Ok(Self::Tab),
                     (KeyCode::Enter   , KeyModifiers::NONE) => Ok(Self::Enter),
+                    (KeyCode::Esc     , KeyModifiers::NONE) => Ok(Self::Dismiss),
 
                     (KeyCode::Up      , KeyModifiers::NONE) => Ok(Self::Move(Direction::Up      )),
                     (KeyCode::Down    , KeyModifiers::NONE) => Ok(Self::Move(Direction::Down    )),
@@ -60,8 +120,32 @@ impl TryFrom<&Event> for EditorCommand {
                     (KeyCode::Home    , KeyModifiers::NONE) => Ok(Self::Move(Direction::Home    )),
                     (KeyCode::End     , KeyModifiers::NONE) => Ok(Self::Move(Direction::End     )),
 
-                    (KeyCode::Backspace, KeyModifiers::NONE) => Ok(Self::Backspace),
+                    (KeyCode::Left    , KeyModifiers::CONTROL) => Ok(Self::Move(Direction::WordLeft     )),
+                    (KeyCode::Right   , KeyModifiers::CONTROL) => Ok(Self::Move(Direction::WordRight    )),
+                    (KeyCode::Home    , KeyModifiers::CONTROL) => Ok(Self::Move(Direction::DocumentStart)),
+                    (KeyCode::End     , KeyModifiers::CONTROL) => Ok(Self::Move(Direction::DocumentEnd  )),
+
+                    (KeyCode::Up      , KeyModifiers::SHIFT) => Ok(Self::Select(Direction::Up      )),
+                    (KeyCode::Down    , KeyModifiers::SHIFT) => Ok(Self::Select(Direction::Down    )),
+                    (KeyCode::Left    , KeyModifiers::SHIFT) => Ok(Self::Select(Direction::Left    )),
+                    (KeyCode::Right   , KeyModifiers::SHIFT) => Ok(Self::Select(Direction::Right   )),
+                    (KeyCode::PageUp  , KeyModifiers::SHIFT) => Ok(Self::Select(Direction::PageUp  )),
+                    (KeyCode::PageDown, KeyModifiers::SHIFT) => Ok(Self::Select(Direction::PageDown)),
+                    (KeyCode::Home    , KeyModifiers::SHIFT) => Ok(Self::Select(Direction::Home    )),
+                    (KeyCode::End     , KeyModifiers::SHIFT) => Ok(Self::Select(Direction::End     )),
+
+                    (KeyCode::Left    , SELECT_BY_WORD) => Ok(Self::Select(Direction::WordLeft     )),
+                    (KeyCode::Right   , SELECT_BY_WORD) => Ok(Self::Select(Direction::WordRight    )),
+                    (KeyCode::Home    , SELECT_BY_WORD) => Ok(Self::Select(Direction::DocumentStart)),
+                    (KeyCode::End     , SELECT_BY_WORD) => Ok(Self::Select(Direction::DocumentEnd  )),
+
+                    // Some terminals send a plain Backspace as Ctrl+H.
+                    (KeyCode::Backspace, KeyModifiers::NONE) |
+                    (KeyCode::Char('h'), KeyModifiers::CONTROL) => Ok(Self::Backspace),
                     (KeyCode::Delete   , KeyModifiers::NONE) => Ok(Self::Delete),
+                    (KeyCode::Backspace, KeyModifiers::CONTROL) |
+                    (KeyCode::Backspace, KeyModifiers::ALT) => Ok(Self::DeleteWordBackward),
+                    (KeyCode::Delete   , KeyModifiers::CONTROL) => Ok(Self::DeleteWordForward),
                     _ => Err(format!("Unsupported key: {:?}", code)),
                 }
             }
@@ -0,0 +1,260 @@
+use std::error::Error;
+use std::fmt;
+
+/// The text encoding of a file on disk.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    Windows1252,
+}
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+// Windows-1252 differs from Latin-1 only in 0x80..=0x9F. The five bytes that
+// are undefined there map to the C1 control with the same value.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž', '\u{8F}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ',
+];
+
+/// Returned when text contains a character the target encoding cannot represent.
+#[derive(Debug)]
+pub struct UnmappableCharacter {
+    pub character: char,
+    pub encoding: Encoding,
+}
+
+impl fmt::Display for UnmappableCharacter {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{:?} cannot be encoded as {}",
+            self.character,
+            self.encoding.name(),
+        )
+    }
+}
+
+impl Error for UnmappableCharacter {}
+
+impl Encoding {
+/// Guesses the encoding of raw file contents.
+///
+/// A byte order mark wins; otherwise text that is valid UTF-8 is UTF-8,
+/// BOM-less text whose every other byte is zero is taken as UTF-16, and
+/// anything else falls back to Windows-1252, which can decode any byte.
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(UTF8_BOM) {
+            return Self::Utf8Bom;
+        }
+        if bytes.starts_with(UTF16LE_BOM) {
+            return Self::Utf16Le;
+        }
+        if bytes.starts_with(UTF16BE_BOM) {
+            return Self::Utf16Be;
+        }
+        if let Some(utf16) = Self::detect_bomless_utf16(bytes) {
+            return utf16;
+        }
+        if std::str::from_utf8(bytes).is_ok() {
+            Self::Utf8
+        } else {
+            Self::Windows1252
+        }
+    }
+
+    // Mostly-ASCII UTF-16 has a zero in every other byte, which plain text
+    // in any of the other encodings practically never has.
+    fn detect_bomless_utf16(bytes: &[u8]) -> Option<Self> {
+        let sample = &bytes[..bytes.len().min(1024) & !1];
+        if sample.is_empty() {
+            return None;
+        }
+        let pairs = sample.len() / 2;
+        let even_zeros = sample.iter().step_by(2).filter(|byte| **byte == 0).count();
+        let odd_zeros = sample.iter().skip(1).step_by(2).filter(|byte| **byte == 0).count();
+        if odd_zeros.saturating_mul(4) >= pairs.saturating_mul(3) && even_zeros == 0 {
+            Some(Self::Utf16Le)
+        } else if even_zeros.saturating_mul(4) >= pairs.saturating_mul(3) && odd_zeros == 0 {
+            Some(Self::Utf16Be)
+        } else {
+            None
+        }
+    }
+
+/// Decodes raw file contents, skipping the byte order mark if present.
+///
+/// Invalid sequences are replaced with U+FFFD rather than failing the load.
+    pub fn decode(self, bytes: &[u8]) -> String {
+        match self {
+            Self::Utf8 | Self::Utf8Bom => {
+                let bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+                String::from_utf8_lossy(bytes).into_owned()
+            }
+            Self::Utf16Le => {
+                let bytes = bytes.strip_prefix(UTF16LE_BOM).unwrap_or(bytes);
+                Self::decode_utf16(bytes, u16::from_le_bytes)
+            }
+            Self::Utf16Be => {
+                let bytes = bytes.strip_prefix(UTF16BE_BOM).unwrap_or(bytes);
+                Self::decode_utf16(bytes, u16::from_be_bytes)
+            }
+            Self::Windows1252 => bytes
+                .iter()
+                .map(|byte| match byte {
+                    0x80..=0x9F => WINDOWS_1252_HIGH[usize::from(byte - 0x80)],
+                    _ => char::from(*byte),
+                })
+                .collect(),
+        }
+    }
+
+    fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> String {
+        let units = bytes
+            .chunks(2)
+            .map(|pair| from_bytes([pair[0], pair.get(1).copied().unwrap_or(0)]));
+        char::decode_utf16(units)
+            .map(|unit| unit.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect()
+    }
+
+/// The byte order mark written at the start of the file, if any.
+///
+/// UTF-16 is always written with one, even if it was detected without.
+    pub const fn bom(self) -> &'static [u8] {
+        match self {
+            Self::Utf8 | Self::Windows1252 => &[],
+            Self::Utf8Bom => UTF8_BOM,
+            Self::Utf16Le => UTF16LE_BOM,
+            Self::Utf16Be => UTF16BE_BOM,
+        }
+    }
+
+/// Encodes text, without the byte order mark.
+    pub fn encode(self, text: &str) -> Result<Vec<u8>, UnmappableCharacter> {
+        match self {
+            Self::Utf8 | Self::Utf8Bom => Ok(text.as_bytes().to_vec()),
+            Self::Utf16Le => Ok(text.encode_utf16().flat_map(u16::to_le_bytes).collect()),
+            Self::Utf16Be => Ok(text.encode_utf16().flat_map(u16::to_be_bytes).collect()),
+            Self::Windows1252 => text
+                .chars()
+                .map(|character| Self::encode_windows_1252(character).ok_or(UnmappableCharacter {
+                    character,
+                    encoding: self,
+                }))
+                .collect(),
+        }
+    }
+
+    fn encode_windows_1252(character: char) -> Option<u8> {
+        if let Some(index) = WINDOWS_1252_HIGH.iter().position(|high| *high == character) {
+            return u8::try_from(index).ok().map(|index| index + 0x80);
+        }
+        match u32::from(character) {
+            0x80..=0x9F => None,
+            code => u8::try_from(code).ok(),
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Utf8 => "UTF-8",
+            Self::Utf8Bom => "UTF-8 BOM",
+            Self::Utf16Le => "UTF-16LE",
+            Self::Utf16Be => "UTF-16BE",
+            Self::Windows1252 => "Windows-1252",
+        }
+    }
+
+/// The next encoding to read a file as. The two UTF-8 variants read alike,
+/// so only one of them is visited.
+    pub const fn next_reading(self) -> Self {
+        match self {
+            Self::Utf8 | Self::Utf8Bom => Self::Utf16Le,
+            other => other.cycled(),
+        }
+    }
+
+/// This encoding, with the UTF-8 variant following whether `bytes` start
+/// with a byte order mark, so reading a file never adds or drops one.
+    pub fn matching_bom(self, bytes: &[u8]) -> Self {
+        match self {
+            Self::Utf8 | Self::Utf8Bom if bytes.starts_with(UTF8_BOM) => Self::Utf8Bom,
+            Self::Utf8 | Self::Utf8Bom => Self::Utf8,
+            other => other,
+        }
+    }
+
+    pub const fn cycled(self) -> Self {
+        match self {
+            Self::Utf8 => Self::Utf8Bom,
+            Self::Utf8Bom => Self::Utf16Le,
+            Self::Utf16Le => Self::Utf16Be,
+            Self::Utf16Be => Self::Windows1252,
+            Self::Windows1252 => Self::Utf8,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_byte_order_marks_and_bomless_utf16() {
+        assert_eq!(Encoding::detect(b"\xEF\xBB\xBFtext"), Encoding::Utf8Bom);
+        assert_eq!(Encoding::detect(b"\xFF\xFEt\0"), Encoding::Utf16Le);
+        assert_eq!(Encoding::detect(b"\xFE\xFF\0t"), Encoding::Utf16Be);
+        assert_eq!(Encoding::detect(b"t\0e\0x\0t\0"), Encoding::Utf16Le);
+        assert_eq!(Encoding::detect(b"\0t\0e\0x\0t"), Encoding::Utf16Be);
+        assert_eq!(Encoding::detect("café".as_bytes()), Encoding::Utf8);
+        assert_eq!(Encoding::detect(b"caf\xE9"), Encoding::Windows1252);
+        assert_eq!(Encoding::detect(b""), Encoding::Utf8);
+    }
+
+    #[test]
+    fn text_survives_a_round_trip() {
+        let text = "naïve “quotes” €5";
+        for encoding in [Encoding::Utf8, Encoding::Utf8Bom, Encoding::Utf16Le, Encoding::Utf16Be, Encoding::Windows1252] {
+            let mut bytes = encoding.bom().to_vec();
+            bytes.extend(encoding.encode(text).unwrap());
+            assert_eq!(Encoding::detect(&bytes), encoding);
+            assert_eq!(encoding.decode(&bytes), text);
+        }
+    }
+
+    #[test]
+    fn windows_1252_maps_its_high_range() {
+        assert_eq!(Encoding::Windows1252.decode(b"\x80\x81\x9F\xFF"), "€\u{81}Ÿÿ");
+        assert_eq!(Encoding::Windows1252.encode("€\u{81}Ÿÿ").unwrap(), b"\x80\x81\x9F\xFF");
+    }
+
+    #[test]
+    fn unmappable_characters_are_reported() {
+        let error = Encoding::Windows1252.encode("ok 日本").unwrap_err();
+        assert_eq!(error.character, '日');
+        assert_eq!(error.to_string(), "'日' cannot be encoded as Windows-1252");
+        assert!(Encoding::Windows1252.encode("\u{80}").is_err());
+    }
+
+    #[test]
+    fn invalid_input_decodes_to_replacement_characters() {
+        assert_eq!(Encoding::Utf8.decode(b"a\xFFb"), "a\u{FFFD}b");
+        assert_eq!(Encoding::Utf16Le.decode(b"\x00\xD8a\x00"), "\u{FFFD}a");
+    }
+
+    #[test]
+    fn reading_keeps_whether_the_file_has_a_bom() {
+        assert_eq!(Encoding::Utf8Bom.matching_bom(b"text"), Encoding::Utf8);
+        assert_eq!(Encoding::Utf8.matching_bom(b"\xEF\xBB\xBFtext"), Encoding::Utf8Bom);
+        assert_eq!(Encoding::Utf16Le.matching_bom(b"text"), Encoding::Utf16Le);
+        assert_eq!(Encoding::Utf8.next_reading(), Encoding::Utf16Le);
+        assert_eq!(Encoding::Windows1252.next_reading(), Encoding::Utf8);
+    }
+}
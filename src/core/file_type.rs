@@ -0,0 +1,41 @@
+use std::fmt;
+use std::path::Path;
+
+/// The kind of document, detected from the file name.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum FileType {
+    Rust,
+    Markdown,
+    Toml,
+    Json,
+    #[default]
+    PlainText,
+}
+
+impl FileType {
+    pub fn from_file_name(file_name: &str) -> Self {
+        let extension = Path::new(file_name)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("rs") => Self::Rust,
+            Some("md" | "markdown") => Self::Markdown,
+            Some("toml") => Self::Toml,
+            Some("json") => Self::Json,
+            _ => Self::PlainText,
+        }
+    }
+}
+
+impl fmt::Display for FileType {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Rust => "Rust",
+            Self::Markdown => "Markdown",
+            Self::Toml => "TOML",
+            Self::Json => "JSON",
+            Self::PlainText => "Text",
+        };
+        write!(formatter, "{name}")
+    }
+}
@@ -0,0 +1,179 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::core::annotated_string::AnnotatedString;
+use crate::core::terminal::{ Position, Size };
+use crate::core::theme::{ Style, Theme };
+
+/// One screen cell: the grapheme drawn there and its style.
+///
+/// A double-width grapheme takes up its cell and the one to its right,
+/// which is left with an empty symbol.
+#[derive(Clone, PartialEq)]
+pub struct Cell {
+    pub symbol: String,
+    pub style: Style,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self { symbol: " ".to_string(), style: Style::default() }
+    }
+}
+
+impl Cell {
+    // Whether this is the right half of a double-width grapheme.
+    fn is_continuation(&self) -> bool {
+        self.symbol.is_empty()
+    }
+
+    /// How many columns the symbol takes up on screen.
+    pub fn width(&self) -> usize {
+        self.symbol.width().max(1)
+    }
+}
+
+/// The grid of cells that makes up the screen.
+///
+/// Everything is drawn into a frame first. Comparing it to the frame that
+/// was drawn last tells which cells changed, so only those need to be sent
+/// to the terminal.
+#[derive(Clone, Default)]
+pub struct Frame {
+    size: Size,
+    cells: Vec<Cell>,
+}
+
+impl Frame {
+    pub fn new(size: Size) -> Self {
+        Self {
+            size,
+            cells: vec![Cell::default(); size.width.saturating_mul(size.height)],
+        }
+    }
+
+    pub const fn size(&self) -> Size {
+        self.size
+    }
+
+/// The cell at `position`, if it is on screen.
+    pub fn cell(&self, position: Position) -> Option<&Cell> {
+        if position.col >= self.size.width {
+            return None;
+        }
+        self.cells.get(position.row.saturating_mul(self.size.width).saturating_add(position.col))
+    }
+
+/// The symbols of a row, joined.
+    pub fn row_text(&self, row: usize) -> String {
+        let start = row.saturating_mul(self.size.width);
+        self.cells
+            .get(start..start.saturating_add(self.size.width))
+            .map_or_else(String::new, |cells| cells.iter().map(|cell| cell.symbol.as_str()).collect())
+    }
+
+/// Puts `cell` at `position`, covering the cell to its right as well if
+/// the symbol is double-width.
+    pub fn set_cell(&mut self, position: Position, cell: Cell) {
+        let Some(cells) = self.row_mut(position.row) else {
+            return ;
+        };
+        let continuation = Cell { symbol: String::new(), style: cell.style };
+        for covered in cells.iter_mut().skip(position.col.saturating_add(1)).take(cell.width().saturating_sub(1)) {
+            *covered = continuation.clone();
+        }
+        if let Some(target) = cells.get_mut(position.col) {
+            *target = cell;
+        }
+    }
+
+    fn row_mut(&mut self, row: usize) -> Option<&mut [Cell]> {
+        let start = row.saturating_mul(self.size.width);
+        self.cells.get_mut(start..start.saturating_add(self.size.width))
+    }
+
+/// Fills a whole row with `line_text` in `style`, and the rest of it with
+/// blanks in the same style.
+    pub fn set_line(&mut self, row: usize, line_text: &str, style: Style) {
+        self.clear_row(row, style);
+        self.set_text(row, 0, line_text, style);
+    }
+
+/// Fills a row like `set_line`, drawing each annotated part of the string
+/// in the theme's style for that annotation on top of `line_style`.
+    pub fn set_annotated_line(
+        &mut self,
+        row: usize,
+        annotated_string: &AnnotatedString,
+        theme: &Theme,
+        line_style: Style,
+    ) {
+        self.clear_row(row, line_style);
+        let mut col = 0;
+        for part in annotated_string.parts() {
+            let style = part
+                .annotation_type
+                .map_or(line_style, |annotation_type| theme.style(annotation_type.scope()).over(line_style));
+            col = self.set_text(row, col, part.string, style);
+        }
+    }
+
+    fn clear_row(&mut self, row: usize, style: Style) {
+        if let Some(cells) = self.row_mut(row) {
+            cells.fill(Cell { symbol: " ".to_string(), style });
+        }
+    }
+
+    // Writes `text` from `col` on, cut off at the edge of the screen, and
+    // returns the column after it.
+    fn set_text(&mut self, row: usize, col: usize, text: &str, style: Style) -> usize {
+        let mut col = col;
+        for grapheme in text.graphemes(true) {
+            let width = grapheme.width();
+            if width == 0 {
+                // Terminals draw these over whatever came before.
+                let previous = col
+                    .checked_sub(1)
+                    .and_then(|previous| self.row_mut(row)?.get_mut(previous));
+                if let Some(previous) = previous {
+                    previous.symbol.push_str(grapheme);
+                }
+                continue;
+            }
+            if col.saturating_add(width) > self.size.width {
+                break;
+            }
+            self.set_cell(Position::new(row, col), Cell { symbol: grapheme.to_string(), style });
+            col = col.saturating_add(width);
+        }
+        col
+    }
+
+/// The cells that differ from `previous`, in screen order.
+///
+/// A double-width grapheme is reported, as a whole, whenever either of
+/// its cells changed.
+    pub fn diff<'a>(&'a self, previous: &Self) -> Vec<(Position, &'a Cell)> {
+        let mut changes: Vec<(Position, &Cell)> = Vec::new();
+        for (index, cell) in self.cells.iter().enumerate() {
+            if previous.size == self.size && previous.cells.get(index) == Some(cell) {
+                continue;
+            }
+            let position = Position::new(index / self.size.width.max(1), index % self.size.width.max(1));
+            if !cell.is_continuation() {
+                changes.push((position, cell));
+                continue;
+            }
+            // The double-width grapheme this belongs to is drawn again instead.
+            let Some(col) = position.col.checked_sub(1) else {
+                continue;
+            };
+            let leading = Position::new(position.row, col);
+            let already_drawn = changes.last().is_some_and(|(last, _)| *last == leading);
+            if !already_drawn && let Some(cell) = self.cell(leading) {
+                changes.push((leading, cell));
+            }
+        }
+        changes
+    }
+}
@@ -0,0 +1,52 @@
+use crate::core::annotated_string::AnnotationType;
+use crate::core::highlighter::{ LineState, Span };
+use crate::core::highlighter::scanner::Scanner;
+
+/// Highlights JSON one line at a time. JSON strings cannot span lines,
+/// so every line starts and ends in the normal state.
+pub struct JsonSyntax;
+
+impl JsonSyntax {
+    pub fn highlight_line(line: &str, state: LineState, spans: &mut Vec<Span>) -> LineState {
+        let mut scanner = Scanner::new(line);
+        while let Some(character) = scanner.peek() {
+            let start = scanner.pos();
+            match character {
+                '"' => {
+                    scanner.bump();
+                    while let Some(character) = scanner.bump() {
+                        match character {
+                            '\\' => {
+                                scanner.bump();
+                            }
+                            '"' => break,
+                            _ => (),
+                        }
+                    }
+                    let annotation_type = if scanner.peek_past_whitespace() == Some(':') {
+                        AnnotationType::Key
+                    } else {
+                        AnnotationType::String
+                    };
+                    scanner.mark(spans, annotation_type, start);
+                }
+                '-' | '0'..='9' => {
+                    scanner.eat_while(|character| {
+                        character.is_ascii_digit() || matches!(character, '-' | '+' | '.' | 'e' | 'E')
+                    });
+                    scanner.mark(spans, AnnotationType::Number, start);
+                }
+                _ if character.is_ascii_alphabetic() => {
+                    let word = scanner.eat_while(|character| character.is_ascii_alphabetic());
+                    if matches!(word, "true" | "false" | "null") {
+                        scanner.mark(spans, AnnotationType::Constant, start);
+                    }
+                }
+                _ => {
+                    scanner.bump();
+                }
+            }
+        }
+        state
+    }
+}
@@ -0,0 +1,99 @@
+use crate::core::annotated_string::AnnotationType;
+use crate::core::highlighter::{ LineState, Span };
+use crate::core::highlighter::scanner::Scanner;
+
+/// Highlights Markdown one line at a time.
+///
+/// Fenced code blocks are carried in the `LineState`; headings, code
+/// spans, emphasis and links are recognised within a line.
+pub struct MarkdownSyntax;
+
+impl MarkdownSyntax {
+    pub fn highlight_line(line: &str, state: LineState, spans: &mut Vec<Span>) -> LineState {
+        let whole_line = (AnnotationType::Code, 0..line.len());
+        let trimmed = line.trim_start();
+        if let LineState::CodeBlock { fence, len } = state {
+            spans.push(whole_line);
+            let closing = trimmed.trim_end();
+            let is_closing = closing.chars().count() >= len && closing.chars().all(|character| character == fence);
+            return if is_closing { LineState::Normal } else { state };
+        }
+        if let Some(fence) = trimmed.chars().next().filter(|fence| matches!(fence, '`' | '~')) {
+            let len = trimmed.chars().take_while(|character| *character == fence).count();
+            if len >= 3 {
+                spans.push(whole_line);
+                return LineState::CodeBlock { fence, len };
+            }
+        }
+        let level = trimmed.chars().take_while(|character| *character == '#').count();
+        if (1..=6).contains(&level) && trimmed[level..].chars().next().is_none_or(char::is_whitespace) {
+            spans.push((AnnotationType::Heading, 0..line.len()));
+            return LineState::Normal;
+        }
+        Self::inline(line, spans);
+        LineState::Normal
+    }
+
+    fn inline(line: &str, spans: &mut Vec<Span>) {
+        let mut scanner = Scanner::new(line);
+        while let Some(character) = scanner.peek() {
+            let start = scanner.pos();
+            match character {
+                '\\' => {
+                    scanner.bump();
+                    scanner.bump();
+                }
+                '`' => {
+                    let ticks = scanner.eat_while(|character| character == '`');
+                    if let Some(end) = scanner.rest().find(ticks) {
+                        scanner.advance(end.saturating_add(ticks.len()));
+                        scanner.mark(spans, AnnotationType::Code, start);
+                    }
+                }
+                // `_` inside a word, as in snake_case, is not emphasis.
+                '_' if line[..start].chars().next_back().is_some_and(char::is_alphanumeric) => {
+                    scanner.bump();
+                }
+                '*' | '_' => {
+                    let delimiter = scanner.eat_while(|next| next == character);
+                    let opens = scanner.peek().is_some_and(|next| !next.is_whitespace());
+                    if opens && let Some(end) = scanner.rest().find(delimiter) {
+                        scanner.advance(end.saturating_add(delimiter.len()));
+                        scanner.mark(spans, AnnotationType::Emphasis, start);
+                    }
+                }
+                '[' => {
+                    scanner.bump();
+                    if Self::link(&mut scanner) {
+                        scanner.mark(spans, AnnotationType::Link, start);
+                    }
+                }
+                '<' if scanner.rest().starts_with("<http") => {
+                    if let Some(end) = scanner.rest().find('>') {
+                        scanner.advance(end.saturating_add(1));
+                        scanner.mark(spans, AnnotationType::Link, start);
+                    } else {
+                        scanner.bump();
+                    }
+                }
+                _ => {
+                    scanner.bump();
+                }
+            }
+        }
+    }
+
+    // After the `[` of `[text](target)`: consumes the rest of the link, or
+    // nothing if the line does not continue like one.
+    fn link(scanner: &mut Scanner) -> bool {
+        let rest = scanner.rest();
+        let Some(text_end) = rest.find("](") else {
+            return false;
+        };
+        let Some(target_len) = rest[text_end..].find(')') else {
+            return false;
+        };
+        scanner.advance(text_end.saturating_add(target_len).saturating_add(1));
+        true
+    }
+}
@@ -0,0 +1,211 @@
+use std::ops::Range;
+
+use ropey::Rope;
+
+use crate::core::annotated_string::{ Annotation, AnnotationType };
+use crate::core::file_type::FileType;
+use crate::core::line::Line;
+
+mod scanner;
+mod rust;
+mod markdown;
+mod toml;
+mod json;
+
+use rust::RustSyntax;
+use markdown::MarkdownSyntax;
+use toml::TomlSyntax;
+use json::JsonSyntax;
+
+/// A styled byte range of a line, as produced by the language highlighters.
+pub type Span = (AnnotationType, Range<usize>);
+
+/// What a line leaves open for the next one, e.g. an unterminated block
+/// comment. A line is highlighted from the state the previous line ended in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineState {
+    #[default]
+    Normal,
+    // Rust block comments nest.
+    BlockComment { depth: usize },
+    String,
+    RawString { hashes: usize },
+    // A fenced Markdown code block, closed by at least `len` of `fence`.
+    CodeBlock { fence: char, len: usize },
+    // A TOML `"""` (basic) or `'''` (literal) string.
+    MultiLineString { literal: bool },
+}
+
+struct HighlightedLine {
+    start_state: LineState,
+    end_state: LineState,
+    annotations: Vec<Annotation>,
+}
+
+/// Syntax highlighting for a whole document, cached per line.
+///
+/// Each cached line remembers the state it started in. Edits only drop
+/// the lines they touch; when highlighting again, a cached line is reused
+/// as long as the line before it still ends in its start state, so an edit
+/// re-highlights from the changed line until the state stabilizes.
+#[derive(Default)]
+pub struct Highlighter {
+    file_type: FileType,
+    lines: Vec<Option<HighlightedLine>>,
+    // Lines before this one are known to be up to date.
+    first_unchecked: usize,
+}
+
+impl Highlighter {
+    pub fn new(file_type: FileType) -> Self {
+        Self { file_type, ..Self::default() }
+    }
+
+    pub const fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+/// Notes that `removed_lines` line breaks after the start of `line_index`
+/// were replaced by `inserted_lines` new ones.
+    pub fn edit(&mut self, line_index: usize, removed_lines: usize, inserted_lines: usize) {
+        if line_index < self.lines.len() {
+            let end = line_index
+                .saturating_add(removed_lines)
+                .saturating_add(1)
+                .min(self.lines.len());
+            let dirty = std::iter::repeat_with(|| None).take(inserted_lines.saturating_add(1));
+            self.lines.splice(line_index..end, dirty);
+        }
+        self.first_unchecked = self.first_unchecked.min(line_index);
+    }
+
+/// Brings the highlighting of all lines before `until` up to date.
+    pub fn update(&mut self, text: &Rope, until: usize) {
+        if self.file_type == FileType::PlainText {
+            return ;
+        }
+        let height = if text.len_chars() == 0 { 0 } else { text.len_lines() };
+        self.lines.resize_with(height, || None);
+        let until = until.min(height);
+
+        let mut state = self
+            .first_unchecked
+            .checked_sub(1)
+            .and_then(|previous| self.lines.get(previous)?.as_ref())
+            .map_or(LineState::Normal, |line| line.end_state);
+        for line_index in self.first_unchecked..until {
+            if let Some(line) = &self.lines[line_index] && line.start_state == state {
+                state = line.end_state;
+                continue;
+            }
+            let mut line = text.line(line_index).to_string();
+            if line.ends_with('\n') {
+                line.pop();
+            }
+            let highlighted = self.highlight_line(&line, state);
+            state = highlighted.end_state;
+            self.lines[line_index] = Some(highlighted);
+        }
+        self.first_unchecked = self.first_unchecked.max(until);
+    }
+
+    fn highlight_line(&self, line: &str, start_state: LineState) -> HighlightedLine {
+        let mut spans = Vec::new();
+        let end_state = match self.file_type {
+            FileType::Rust => RustSyntax::highlight_line(line, start_state, &mut spans),
+            FileType::Markdown => MarkdownSyntax::highlight_line(line, start_state, &mut spans),
+            FileType::Toml => TomlSyntax::highlight_line(line, start_state, &mut spans),
+            FileType::Json => JsonSyntax::highlight_line(line, start_state, &mut spans),
+            FileType::PlainText => start_state,
+        };
+        let graphemes = Line::from(line);
+        let annotations = spans
+            .into_iter()
+            .map(|(annotation_type, bytes)| {
+                let Range { start, end } = graphemes.grapheme_range(bytes);
+                Annotation { annotation_type, start, end }
+            })
+            .collect();
+        HighlightedLine { start_state, end_state, annotations }
+    }
+
+/// The syntax annotations of a line, empty unless `update` covered it.
+    pub fn annotations(&self, line_index: usize) -> &[Annotation] {
+        self.lines
+            .get(line_index)
+            .and_then(Option::as_ref)
+            .map_or(&[], |line| line.annotations.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn end_states(highlighter: &Highlighter) -> Vec<Option<LineState>> {
+        highlighter.lines.iter().map(|line| line.as_ref().map(|line| line.end_state)).collect()
+    }
+
+    // The grapheme ranges annotated as comments on a line.
+    fn comments(highlighter: &Highlighter, line_index: usize) -> Vec<(usize, usize)> {
+        highlighter
+            .annotations(line_index)
+            .iter()
+            .filter(|annotation| annotation.annotation_type == AnnotationType::Comment)
+            .map(|annotation| (annotation.start, annotation.end))
+            .collect()
+    }
+
+    #[test]
+    fn block_comments_carry_over_to_the_next_lines() {
+        let text = Rope::from_str("a /* b /* c\nd */ e\nf */ g\nh");
+        let mut highlighter = Highlighter::new(FileType::Rust);
+        highlighter.update(&text, 4);
+        assert_eq!(end_states(&highlighter), [
+            Some(LineState::BlockComment { depth: 2 }),
+            Some(LineState::BlockComment { depth: 1 }),
+            Some(LineState::Normal),
+            Some(LineState::Normal),
+        ]);
+        assert_eq!(comments(&highlighter, 1), [(0, 6)]);
+        assert_eq!(comments(&highlighter, 2), [(0, 4)]);
+        assert!(comments(&highlighter, 3).is_empty());
+    }
+
+    #[test]
+    fn only_lines_up_to_the_limit_are_highlighted() {
+        let text = Rope::from_str("// one\n// two\n// three");
+        let mut highlighter = Highlighter::new(FileType::Rust);
+        highlighter.update(&text, 2);
+        assert_eq!(end_states(&highlighter), [Some(LineState::Normal), Some(LineState::Normal), None]);
+        assert!(highlighter.annotations(2).is_empty());
+    }
+
+    #[test]
+    fn an_edit_rehighlights_until_the_state_settles() {
+        let mut text = Rope::from_str("a\nb\nc");
+        let mut highlighter = Highlighter::new(FileType::Rust);
+        highlighter.update(&text, 3);
+        assert!(comments(&highlighter, 2).is_empty());
+
+        text.insert(0, "/*");
+        highlighter.edit(0, 0, 0);
+        highlighter.update(&text, 3);
+        assert_eq!(comments(&highlighter, 2), [(0, 1)]);
+
+        // Joining the first two lines keeps the following line in the comment.
+        text.remove(3..4);
+        highlighter.edit(0, 1, 0);
+        assert_eq!(highlighter.lines.len(), 2);
+        highlighter.update(&text, 2);
+        assert_eq!(comments(&highlighter, 1), [(0, 1)]);
+    }
+
+    #[test]
+    fn plain_text_is_left_alone() {
+        let text = Rope::from_str("/* nothing */");
+        let mut highlighter = Highlighter::new(FileType::PlainText);
+        highlighter.update(&text, 1);
+        assert!(highlighter.annotations(0).is_empty());
+    }
+}
@@ -0,0 +1,227 @@
+use crate::core::annotated_string::AnnotationType;
+use crate::core::highlighter::{ LineState, Span };
+use crate::core::highlighter::scanner::Scanner;
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+    "extern", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+    "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "type",
+    "union", "unsafe", "use", "where", "while", "yield",
+];
+
+const PRIMITIVE_TYPES: &[&str] = &[
+    "bool", "char", "str", "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32",
+    "u64", "u128", "usize", "f32", "f64",
+];
+
+const CONSTANTS: &[&str] = &["true", "false", "None", "Some", "Ok", "Err"];
+
+/// Highlights Rust source one line at a time.
+///
+/// Block comments (which nest), strings and raw strings may run over
+/// several lines and are carried in the `LineState`.
+pub struct RustSyntax;
+
+impl RustSyntax {
+    pub fn highlight_line(line: &str, state: LineState, spans: &mut Vec<Span>) -> LineState {
+        let mut scanner = Scanner::new(line);
+        let mut state = state;
+        while !scanner.is_done() {
+            let start = scanner.pos();
+            state = match state {
+                LineState::BlockComment { depth } => {
+                    let state = Self::block_comment(&mut scanner, depth);
+                    scanner.mark(spans, AnnotationType::Comment, start);
+                    state
+                }
+                LineState::String => {
+                    let state = Self::string(&mut scanner);
+                    scanner.mark(spans, AnnotationType::String, start);
+                    state
+                }
+                LineState::RawString { hashes } => {
+                    let state = Self::raw_string(&mut scanner, hashes);
+                    scanner.mark(spans, AnnotationType::String, start);
+                    state
+                }
+                _ => Self::token(&mut scanner, spans),
+            };
+        }
+        state
+    }
+
+    // Lexes one token in normal code. Returns the state of a string or
+    // comment it opens, if any.
+    fn token(scanner: &mut Scanner, spans: &mut Vec<Span>) -> LineState {
+        let start = scanner.pos();
+        if scanner.starts_with("//") {
+            scanner.skip_to_end();
+            scanner.mark(spans, AnnotationType::Comment, start);
+            return LineState::Normal;
+        }
+        if scanner.eat("/*") {
+            let state = Self::block_comment(scanner, 1);
+            scanner.mark(spans, AnnotationType::Comment, start);
+            return state;
+        }
+        if scanner.eat("\"") || scanner.eat("b\"") || scanner.eat("c\"") {
+            let state = Self::string(scanner);
+            scanner.mark(spans, AnnotationType::String, start);
+            return state;
+        }
+        if let Some(hashes) = Self::raw_string_start(scanner) {
+            let state = Self::raw_string(scanner, hashes);
+            scanner.mark(spans, AnnotationType::String, start);
+            return state;
+        }
+        if scanner.starts_with("b'") {
+            scanner.bump();
+        }
+        match scanner.peek() {
+            Some('\'') => Self::quote(scanner, spans),
+            Some(character) if character.is_ascii_digit() => {
+                Self::number(scanner);
+                scanner.mark(spans, AnnotationType::Number, start);
+            }
+            Some('#') if matches!(scanner.peek_nth(1), Some('[' | '!')) => {
+                Self::attribute(scanner);
+                scanner.mark(spans, AnnotationType::Attribute, start);
+            }
+            Some(character) if character.is_alphabetic() || character == '_' => {
+                Self::identifier(scanner, spans);
+            }
+            _ => {
+                scanner.bump();
+            }
+        }
+        LineState::Normal
+    }
+
+    fn block_comment(scanner: &mut Scanner, depth: usize) -> LineState {
+        let mut depth = depth;
+        while !scanner.is_done() {
+            if scanner.eat("/*") {
+                depth = depth.saturating_add(1);
+            } else if scanner.eat("*/") {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    return LineState::Normal;
+                }
+            } else {
+                scanner.bump();
+            }
+        }
+        LineState::BlockComment { depth }
+    }
+
+    // The body of a string after its opening quote.
+    fn string(scanner: &mut Scanner) -> LineState {
+        while let Some(character) = scanner.bump() {
+            match character {
+                '\\' => {
+                    scanner.bump();
+                }
+                '"' => return LineState::Normal,
+                _ => (),
+            }
+        }
+        LineState::String
+    }
+
+    // Consumes the opening of a raw string such as `r#"` and returns its number of `#`s.
+    fn raw_string_start(scanner: &mut Scanner) -> Option<usize> {
+        let prefix_len = if scanner.starts_with("br") || scanner.starts_with("cr") {
+            2
+        } else if scanner.starts_with("r") {
+            1
+        } else {
+            return None;
+        };
+        let hashes = scanner.rest()[prefix_len..].chars().take_while(|character| *character == '#').count();
+        if scanner.rest()[prefix_len.saturating_add(hashes)..].starts_with('"') {
+            scanner.advance(prefix_len.saturating_add(hashes).saturating_add(1));
+            Some(hashes)
+        } else {
+            None
+        }
+    }
+
+    fn raw_string(scanner: &mut Scanner, hashes: usize) -> LineState {
+        let closing = format!("\"{}", "#".repeat(hashes));
+        while !scanner.is_done() {
+            if scanner.eat(&closing) {
+                return LineState::Normal;
+            }
+            scanner.bump();
+        }
+        LineState::RawString { hashes }
+    }
+
+    // A char literal such as `'a'` or `'\n'`, or a lifetime such as `'a`.
+    fn quote(scanner: &mut Scanner, spans: &mut Vec<Span>) {
+        let start = scanner.pos();
+        scanner.bump();
+        if scanner.peek() == Some('\\') {
+            scanner.bump();
+            scanner.bump();
+            scanner.eat_while(|character| character != '\'');
+            scanner.bump();
+            scanner.mark(spans, AnnotationType::Character, start);
+        } else if scanner.peek_nth(1) == Some('\'') {
+            scanner.bump();
+            scanner.bump();
+            scanner.mark(spans, AnnotationType::Character, start);
+        } else {
+            scanner.eat_while(|character| character.is_alphanumeric() || character == '_');
+            scanner.mark(spans, AnnotationType::Lifetime, start);
+        }
+    }
+
+    fn number(scanner: &mut Scanner) {
+        scanner.eat_while(|character| character.is_ascii_alphanumeric() || character == '_');
+        // A fraction, but not a range (`1..2`) or a method call (`1.max(2)`).
+        if scanner.peek() == Some('.') && scanner.peek_nth(1).is_some_and(|next| next.is_ascii_digit()) {
+            scanner.bump();
+            scanner.eat_while(|character| character.is_ascii_alphanumeric() || character == '_');
+        }
+    }
+
+    // `#[...]` or `#![...]`, up to the matching bracket on this line.
+    fn attribute(scanner: &mut Scanner) {
+        let mut depth: usize = 0;
+        while let Some(character) = scanner.bump() {
+            match character {
+                '[' => depth = depth.saturating_add(1),
+                ']' => {
+                    depth = depth.saturating_sub(1);
+                    if depth == 0 {
+                        return ;
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn identifier(scanner: &mut Scanner, spans: &mut Vec<Span>) {
+        let start = scanner.pos();
+        let word = scanner.eat_while(|character| character.is_alphanumeric() || character == '_');
+        if scanner.peek() == Some('!') && scanner.peek_nth(1) != Some('=') {
+            scanner.bump();
+            scanner.mark(spans, AnnotationType::Macro, start);
+            return ;
+        }
+        let annotation_type = if KEYWORDS.contains(&word) {
+            AnnotationType::Keyword
+        } else if CONSTANTS.contains(&word) {
+            AnnotationType::Constant
+        } else if PRIMITIVE_TYPES.contains(&word) || word.starts_with(char::is_uppercase) {
+            AnnotationType::Type
+        } else if scanner.peek() == Some('(') {
+            AnnotationType::Function
+        } else {
+            return ;
+        };
+        scanner.mark(spans, annotation_type, start);
+    }
+}
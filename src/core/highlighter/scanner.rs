@@ -0,0 +1,85 @@
+use crate::core::annotated_string::AnnotationType;
+use crate::core::highlighter::Span;
+
+/// Walks a line char by char for the language highlighters.
+pub struct Scanner<'a> {
+    line: &'a str,
+    pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+    pub const fn new(line: &'a str) -> Self {
+        Self { line, pos: 0 }
+    }
+
+/// The byte index of the next char.
+    pub const fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub const fn is_done(&self) -> bool {
+        self.pos >= self.line.len()
+    }
+
+    pub fn rest(&self) -> &'a str {
+        &self.line[self.pos..]
+    }
+
+    pub fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+/// The char `offset` chars after the next one.
+    pub fn peek_nth(&self, offset: usize) -> Option<char> {
+        self.rest().chars().nth(offset)
+    }
+
+    pub fn bump(&mut self) -> Option<char> {
+        let next = self.peek()?;
+        self.pos = self.pos.saturating_add(next.len_utf8());
+        Some(next)
+    }
+
+    pub fn starts_with(&self, prefix: &str) -> bool {
+        self.rest().starts_with(prefix)
+    }
+
+/// Skips `len` bytes, which must end on a char boundary.
+    pub fn advance(&mut self, len: usize) {
+        self.pos = self.pos.saturating_add(len).min(self.line.len());
+    }
+
+/// Consumes `prefix` if the rest of the line starts with it.
+    pub fn eat(&mut self, prefix: &str) -> bool {
+        if self.starts_with(prefix) {
+            self.pos = self.pos.saturating_add(prefix.len());
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn eat_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let start = self.pos;
+        while self.peek().is_some_and(&predicate) {
+            self.bump();
+        }
+        &self.line[start..self.pos]
+    }
+
+    pub fn skip_to_end(&mut self) {
+        self.pos = self.line.len();
+    }
+
+/// The next char that is not whitespace, without consuming anything.
+    pub fn peek_past_whitespace(&self) -> Option<char> {
+        self.rest().trim_start().chars().next()
+    }
+
+/// Marks everything from `start` up to here.
+    pub fn mark(&self, spans: &mut Vec<Span>, annotation_type: AnnotationType, start: usize) {
+        if start < self.pos {
+            spans.push((annotation_type, start..self.pos));
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use crate::core::annotated_string::AnnotationType;
+use crate::core::highlighter::{ LineState, Span };
+use crate::core::highlighter::scanner::Scanner;
+
+/// Highlights TOML one line at a time.
+///
+/// Multi-line `"""` and `'''` strings are carried in the `LineState`.
+pub struct TomlSyntax;
+
+impl TomlSyntax {
+    pub fn highlight_line(line: &str, state: LineState, spans: &mut Vec<Span>) -> LineState {
+        let mut scanner = Scanner::new(line);
+        let mut state = state;
+        if let LineState::MultiLineString { literal } = state {
+            state = Self::multi_line_string(&mut scanner, literal);
+            scanner.mark(spans, AnnotationType::String, 0);
+        } else if line.trim_start().starts_with('[') {
+            scanner.eat_while(char::is_whitespace);
+            let start = scanner.pos();
+            scanner.eat_while(|character| character != ']' && character != '#');
+            scanner.eat_while(|character| character == ']');
+            scanner.mark(spans, AnnotationType::Section, start);
+        }
+        while state == LineState::Normal && !scanner.is_done() {
+            state = Self::token(&mut scanner, spans);
+        }
+        state
+    }
+
+    fn token(scanner: &mut Scanner, spans: &mut Vec<Span>) -> LineState {
+        let start = scanner.pos();
+        match scanner.peek() {
+            Some('#') => {
+                scanner.skip_to_end();
+                scanner.mark(spans, AnnotationType::Comment, start);
+            }
+            Some(quote @ ('"' | '\'')) => {
+                let triple = if quote == '"' { "\"\"\"" } else { "'''" };
+                if scanner.eat(triple) {
+                    let state = Self::multi_line_string(scanner, quote == '\'');
+                    scanner.mark(spans, AnnotationType::String, start);
+                    return state;
+                }
+                scanner.bump();
+                Self::string(scanner, quote);
+                let is_key = scanner.peek_past_whitespace().is_some_and(|next| matches!(next, '=' | '.'));
+                let annotation_type = if is_key { AnnotationType::Key } else { AnnotationType::String };
+                scanner.mark(spans, annotation_type, start);
+            }
+            Some(character) if character.is_ascii_digit() || matches!(character, '+' | '-') => {
+                // Numbers, dates and times.
+                scanner.eat_while(|character| {
+                    character.is_ascii_alphanumeric() || matches!(character, '_' | ':' | '.' | '+' | '-')
+                });
+                let is_key = scanner.peek_past_whitespace() == Some('=');
+                let annotation_type = if is_key { AnnotationType::Key } else { AnnotationType::Number };
+                scanner.mark(spans, annotation_type, start);
+            }
+            Some(character) if character.is_ascii_alphabetic() || character == '_' => {
+                let word = scanner.eat_while(|character| {
+                    character.is_ascii_alphanumeric() || matches!(character, '_' | '-')
+                });
+                let annotation_type = if scanner.peek_past_whitespace().is_some_and(|next| matches!(next, '=' | '.')) {
+                    AnnotationType::Key
+                } else if matches!(word, "true" | "false") {
+                    AnnotationType::Constant
+                } else if matches!(word, "inf" | "nan") {
+                    AnnotationType::Number
+                } else {
+                    return LineState::Normal;
+                };
+                scanner.mark(spans, annotation_type, start);
+            }
+            _ => {
+                scanner.bump();
+            }
+        }
+        LineState::Normal
+    }
+
+    // A single-line string after its opening quote. Literal strings have no escapes.
+    fn string(scanner: &mut Scanner, quote: char) {
+        while let Some(character) = scanner.bump() {
+            if character == quote {
+                return ;
+            }
+            if character == '\\' && quote == '"' {
+                scanner.bump();
+            }
+        }
+    }
+
+    fn multi_line_string(scanner: &mut Scanner, literal: bool) -> LineState {
+        let closing = if literal { "'''" } else { "\"\"\"" };
+        while !scanner.is_done() {
+            if scanner.eat(closing) {
+                // Up to two more quotes still belong to the string.
+                let quote = if literal { '\'' } else { '"' };
+                scanner.eat_while(|character| character == quote);
+                return LineState::Normal;
+            }
+            // An escaped quote does not close a basic string.
+            if !literal {
+                scanner.eat("\\");
+            }
+            scanner.bump();
+        }
+        LineState::MultiLineString { literal }
+    }
+}
@@ -0,0 +1,276 @@
+/// A single primitive change to the buffer text.
+///
+/// `at` is a char index into the buffer's rope.
+#[derive(Clone)]
+pub enum Edit {
+    Insert { at: usize, text: String },
+    Delete { at: usize, text: String },
+}
+
+impl Edit {
+    /// Returns the edit that reverts `self`.
+    pub fn inverse(&self) -> Self {
+        match self {
+            Self::Insert { at, text } => Self::Delete { at: *at, text: text.clone() },
+            Self::Delete { at, text } => Self::Insert { at: *at, text: text.clone() },
+        }
+    }
+
+    pub const fn at(&self) -> usize {
+        match self {
+            Self::Insert { at, .. } | Self::Delete { at, .. } => *at,
+        }
+    }
+
+    // The char index right after the text this edit inserted,
+    // or the position the deleted text used to start at.
+    fn end(&self) -> usize {
+        match self {
+            Self::Insert { at, text } => at.saturating_add(text.chars().count()),
+            Self::Delete { at, .. } => *at,
+        }
+    }
+}
+
+/// A group of edits that is undone and redone as one step.
+#[derive(Clone, Default)]
+pub struct Transaction {
+    pub edits: Vec<Edit>,
+    // Whether this transaction is a run of typed characters that may still grow.
+    typing: bool,
+}
+
+impl Transaction {
+    /// Where the cursor belongs once this transaction has been undone.
+    pub fn undo_cursor(&self) -> usize {
+        self.edits.first().map_or(0, Edit::at)
+    }
+
+    /// Where the cursor belongs once this transaction has been redone.
+    pub fn redo_cursor(&self) -> usize {
+        self.edits.last().map_or(0, Edit::end)
+    }
+
+    fn extends_typing(&self, edit: &Edit) -> bool {
+        if !self.typing {
+            return false;
+        }
+        match (self.edits.last(), edit) {
+            (Some(last @ Edit::Insert { .. }), Edit::Insert { at, .. }) => last.end() == *at,
+            _ => false,
+        }
+    }
+}
+
+/// Undo/redo stacks of transactions.
+///
+/// Edits are collected into an open transaction until it is sealed,
+/// either explicitly or because the next edit cannot be merged into it.
+#[derive(Default)]
+pub struct History {
+    undo_stack: Vec<Transaction>,
+    redo_stack: Vec<Transaction>,
+    current: Option<Transaction>,
+    group_depth: usize,
+}
+
+impl History {
+/// Records an edit that has already been applied to the buffer.
+///
+/// With `typing` set, consecutive inserts that continue each other are
+/// merged into one undo step.
+    pub fn record(&mut self, edit: Edit, typing: bool) {
+        self.redo_stack.clear();
+        let extends = self
+            .current
+            .as_ref()
+            .is_some_and(|current| typing && current.extends_typing(&edit));
+        if !extends && self.group_depth == 0 {
+            self.seal();
+        }
+        let current = self.current.get_or_insert_with(|| Transaction {
+            edits: Vec::new(),
+            typing,
+        });
+        current.typing &= typing;
+        current.edits.push(edit);
+    }
+
+/// Closes the open transaction so that the next edit starts a new undo step.
+    pub fn seal(&mut self) {
+        if self.group_depth > 0 {
+            return ;
+        }
+        if let Some(current) = self.current.take() && !current.edits.is_empty() {
+            self.undo_stack.push(current);
+        }
+    }
+
+/// Starts a group: every edit until the matching `end_group` becomes a single undo step.
+    pub fn begin_group(&mut self) {
+        self.seal();
+        self.group_depth = self.group_depth.saturating_add(1);
+    }
+
+    pub fn end_group(&mut self) {
+        self.group_depth = self.group_depth.saturating_sub(1);
+        self.seal();
+    }
+
+/// Pops the most recent transaction. The caller applies its inverse.
+    pub fn undo(&mut self) -> Option<Transaction> {
+        self.seal();
+        let transaction = self.undo_stack.pop()?;
+        self.redo_stack.push(transaction.clone());
+        Some(transaction)
+    }
+
+/// Pops the most recently undone transaction. The caller applies it again.
+    pub fn redo(&mut self) -> Option<Transaction> {
+        self.seal();
+        let transaction = self.redo_stack.pop()?;
+        self.undo_stack.push(transaction.clone());
+        Some(transaction)
+    }
+}
+
+// Serialization used to persist the history next to a file's state.
+//
+// The format is line based; edit texts are written with a byte length
+// prefix so they may contain line breaks.
+impl History {
+    pub fn serialize(&self) -> String {
+        let mut undo_stack = self.undo_stack.clone();
+        if let Some(current) = &self.current {
+            undo_stack.push(current.clone());
+        }
+        let mut out = String::new();
+        Self::serialize_stack(&mut out, "undo", &undo_stack);
+        Self::serialize_stack(&mut out, "redo", &self.redo_stack);
+        out
+    }
+
+    fn serialize_stack(out: &mut String, name: &str, stack: &[Transaction]) {
+        out.push_str(&format!("{name} {}\n", stack.len()));
+        for transaction in stack {
+            out.push_str(&format!("transaction {}\n", transaction.edits.len()));
+            for edit in &transaction.edits {
+                let (kind, at, text) = match edit {
+                    Edit::Insert { at, text } => ("insert", at, text),
+                    Edit::Delete { at, text } => ("delete", at, text),
+                };
+                out.push_str(&format!("{kind} {at} {}\n{text}\n", text.len()));
+            }
+        }
+    }
+
+    pub fn deserialize(input: &str) -> Option<Self> {
+        let mut rest = input;
+        let undo_stack = Self::deserialize_stack(&mut rest, "undo")?;
+        let redo_stack = Self::deserialize_stack(&mut rest, "redo")?;
+        Some(Self { undo_stack, redo_stack, current: None, group_depth: 0 })
+    }
+
+    fn deserialize_stack(rest: &mut &str, name: &str) -> Option<Vec<Transaction>> {
+        let count: usize = Self::take_header(rest, name)?.first()?.parse().ok()?;
+        let mut stack = Vec::with_capacity(count);
+        for _ in 0..count {
+            let edit_count: usize = Self::take_header(rest, "transaction")?.first()?.parse().ok()?;
+            let mut edits = Vec::with_capacity(edit_count);
+            for _ in 0..edit_count {
+                let (kind, fields) = Self::take_line(rest)?.split_once(' ')?;
+                let (at, len) = fields.split_once(' ')?;
+                let at: usize = at.parse().ok()?;
+                let len: usize = len.parse().ok()?;
+                let text = rest.get(..len)?.to_string();
+                *rest = rest.get(len..)?.strip_prefix('\n')?;
+                edits.push(match kind {
+                    "insert" => Edit::Insert { at, text },
+                    "delete" => Edit::Delete { at, text },
+                    _ => return None,
+                });
+            }
+            stack.push(Transaction { edits, typing: false });
+        }
+        Some(stack)
+    }
+
+    // Reads a `<name> <fields...>` line and returns the fields.
+    fn take_header<'a>(rest: &mut &'a str, name: &str) -> Option<Vec<&'a str>> {
+        let mut fields = Self::take_line(rest)?.split(' ');
+        if fields.next()? != name {
+            return None;
+        }
+        Some(fields.collect())
+    }
+
+    fn take_line<'a>(rest: &mut &'a str) -> Option<&'a str> {
+        let (line, remainder) = rest.split_once('\n')?;
+        *rest = remainder;
+        Some(line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(at: usize, text: &str) -> Edit {
+        Edit::Insert { at, text: text.to_string() }
+    }
+
+    fn delete(at: usize, text: &str) -> Edit {
+        Edit::Delete { at, text: text.to_string() }
+    }
+
+    // How many edits each transaction on the undo stack holds, oldest first.
+    fn undo_steps(history: &History) -> Vec<usize> {
+        history.undo_stack.iter().map(|transaction| transaction.edits.len()).collect()
+    }
+
+    #[test]
+    fn typing_that_continues_is_one_step() {
+        let mut history = History::default();
+        history.record(insert(0, "a"), true);
+        history.record(insert(1, "b"), true);
+        history.record(insert(5, "c"), true);
+        history.record(insert(6, "\n"), false);
+        history.seal();
+        assert_eq!(undo_steps(&history), [2, 1, 1]);
+    }
+
+    #[test]
+    fn a_group_is_one_step_even_when_nested() {
+        let mut history = History::default();
+        history.begin_group();
+        history.record(delete(0, "x"), false);
+        history.begin_group();
+        history.record(insert(0, "y"), false);
+        history.end_group();
+        history.record(insert(1, "z"), false);
+        history.end_group();
+        assert_eq!(undo_steps(&history), [3]);
+    }
+
+    #[test]
+    fn undo_and_redo_move_transactions_between_the_stacks() {
+        let mut history = History::default();
+        history.record(insert(0, "one"), false);
+        history.record(delete(1, "n"), false);
+        let undone = history.undo().unwrap();
+        assert_eq!((undone.undo_cursor(), undone.redo_cursor()), (1, 1));
+        let undone = history.undo().unwrap();
+        assert_eq!((undone.undo_cursor(), undone.redo_cursor()), (0, 3));
+        assert!(history.undo().is_none());
+        assert!(history.redo().is_some());
+        history.record(insert(0, "new"), false);
+        assert!(history.redo().is_none());
+        assert_eq!(undo_steps(&history), [1, 1]);
+    }
+
+    #[test]
+    fn inverse_swaps_insert_and_delete() {
+        assert!(matches!(insert(2, "ab").inverse(), Edit::Delete { at: 2, text } if text == "ab"));
+        assert!(matches!(delete(2, "ab").inverse(), Edit::Insert { at: 2, text } if text == "ab"));
+    }
+}
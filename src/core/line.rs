@@ -4,6 +4,9 @@ use std::ops::Range;
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
+use crate::core::annotated_string::{ AnnotatedString, Annotation, AnnotationType };
+use crate::core::pattern::Pattern;
+
 #[derive(Clone, Copy)]
 enum GraphemeWidth {
     Half,
@@ -23,24 +26,26 @@ struct TextFragment {
     grapheme: String,
     rendered_width: GraphemeWidth,
     replacement: Option<char>,
+    start_byte_index: usize,
 }
 
 #[derive(Default)]
 pub struct Line {
     fragments: Vec<TextFragment>,
+    string: String,
 }
 
 impl Line {
 
     pub fn from(line_str: &str) -> Self {
         let fragments = Self::str_to_fragments(line_str);
-        Self { fragments }
+        Self { fragments, string: line_str.to_string() }
     }
 
     fn str_to_fragments(line_str: &str) -> Vec<TextFragment> {
         line_str
-        .graphemes(true)
-        .map(|grapheme| {
+        .grapheme_indices(true)
+        .map(|(start_byte_index, grapheme)| {
             let (replacement, rendered_width) = 
                 Self::replacement_character(grapheme)
                 .map_or_else(
@@ -59,6 +64,7 @@ impl Line {
                 grapheme: grapheme.to_string(),
                 rendered_width,
                 replacement,
+                start_byte_index,
             }
         })
         .collect()
@@ -72,10 +78,8 @@ impl Line {
             _ if width > 0 && for_str.trim().is_empty() => Some('␣'),
             _ if width == 0 => {
                 let mut chars = for_str.chars();
-                if let Some(ch) = chars.next() {
-                    if ch.is_control() && chars.next().is_none() {
-                        return Some('▯');
-                    }
+                if let Some(ch) = chars.next() && ch.is_control() && chars.next().is_none() {
+                    return Some('▯');
                 }
                 Some('·')
             }
@@ -83,37 +87,57 @@ impl Line {
         }
     }
 
-/// **Not very safe**
-    pub fn get_visible_graphemes(&self, range: Range<usize>) -> String {
+/// Returns the part of the line that is visible between the columns in `range`.
+///
+/// Graphemes cut off by either edge are shown as `⋯`. That and the other
+/// replacement glyphs are annotated as such. `annotations` refer to grapheme
+/// indices of the line and are carried over to the visible part.
+    pub fn get_annotated_visible_substr(
+        &self,
+        range: Range<usize>,
+        annotations: &[Annotation],
+    ) -> AnnotatedString {
         let Range{ start, end } = range;
+        let mut result = AnnotatedString::default();
         if start >= end {
-            return "".to_string();
+            return result;
         }
-        
-        let mut result = String::new();
+
+        // Byte range in `result` of every grapheme that made it on screen.
+        let mut visible = Vec::new();
         let mut current_pos = 0;
-        for fragment in &self.fragments {
+        for (grapheme_index, fragment) in self.fragments.iter().enumerate() {
             let fragment_end = fragment.rendered_width.saturating_add(current_pos);
             if current_pos >= end {
                 break;
             }
             if fragment_end > start {
+                let byte_start = result.len();
                 if fragment_end > end || current_pos < start {
                     // Clip on the right or left
                     result.push('⋯');
+                    result.add_annotation(AnnotationType::Replacement, byte_start..result.len());
                 } else if let Some(char) = fragment.replacement {
                     result.push(char);
+                    result.add_annotation(AnnotationType::Replacement, byte_start..result.len());
                 } else {
                     result.push_str(&fragment.grapheme);
                 }
+                visible.push((grapheme_index, byte_start..result.len()));
             }
             current_pos = fragment_end;
         }
-        result
-    }
 
-    pub fn len(&self) -> usize {
-        self.fragments.len()
+        for annotation in annotations {
+            let mut covered = visible
+                .iter()
+                .filter(|(index, _)| annotation.start <= *index && *index < annotation.end);
+            if let Some((_, first)) = covered.next() {
+                let last_end = covered.next_back().map_or(first.end, |(_, bytes)| bytes.end);
+                result.add_annotation(annotation.annotation_type, first.start..last_end);
+            }
+        }
+        result
     }
 
     /// Returns the rendered width up to the specified grapheme index.
@@ -125,57 +149,226 @@ impl Line {
         width
     }
 
-    pub fn grapheme_count(&self) -> usize {
-        self.fragments.len()
+/// Where each row of the line starts, as grapheme indices, when it is
+/// wrapped to rows of at most `width` columns.
+///
+/// Rows break before the word that no longer fits, using the Unicode word
+/// boundaries, and only inside a word that is wider than a whole row.
+/// Whitespace never starts a new row: it may hang past the edge. A line
+/// that exactly fills its last row gets an empty row after it, so the
+/// cursor has a place at its end.
+    pub fn wrap_starts(&self, width: usize) -> Vec<usize> {
+        let word_starts: Vec<usize> = self
+            .string
+            .split_word_bound_indices()
+            .filter(|(_, word)| !word.trim().is_empty())
+            .filter_map(|(byte_index, _)| self.byte_index_to_grapheme_index(byte_index))
+            .collect();
+        let mut starts = vec![0];
+        let mut row_start = 0;
+        let mut row_width: usize = 0;
+        for (grapheme_index, fragment) in self.fragments.iter().enumerate() {
+            let is_whitespace = fragment.grapheme.trim().is_empty();
+            if !is_whitespace && grapheme_index > row_start && fragment.rendered_width.saturating_add(row_width) > width {
+                // Break before the word this grapheme belongs to, if it starts on this row.
+                let word_start = word_starts[..word_starts.partition_point(|start| *start <= grapheme_index)]
+                    .last()
+                    .copied()
+                    .filter(|start| *start > row_start)
+                    .unwrap_or(grapheme_index);
+                starts.push(word_start);
+                row_start = word_start;
+                row_width = self.width_until(grapheme_index).saturating_sub(self.width_until(word_start));
+            }
+            row_width = fragment.rendered_width.saturating_add(row_width);
+        }
+        if row_width >= width && self.fragments.len() > row_start {
+            starts.push(self.fragments.len());
+        }
+        starts
     }
 
-     pub fn insert_char(&mut self, character: char, grapheme_index: usize) {
-        let mut result = String::new();
-
-        for (index, fragment) in self.fragments.iter().enumerate() {
-            if index == grapheme_index {
-                result.push(character);
+/// The grapheme among `graphemes` that is drawn at `column`, counted from
+/// the first of them, or the end of the range if they are narrower.
+    pub fn grapheme_at_column(&self, graphemes: Range<usize>, column: usize) -> usize {
+        let mut width: usize = 0;
+        for grapheme_index in graphemes.clone() {
+            let Some(fragment) = self.fragments.get(grapheme_index) else {
+                break;
+            };
+            width = fragment.rendered_width.saturating_add(width);
+            if width > column {
+                return grapheme_index;
             }
-            result.push_str(&fragment.grapheme);
         }
-        if grapheme_index >= self.fragments.len() {
-            result.push(character);
+        graphemes.end
+    }
+
+/// The graphemes of the word, run of whitespace or punctuation at
+/// `grapheme_index`, split at Unicode word boundaries. Past the end of the
+/// line, the last of them.
+    pub fn word_at(&self, grapheme_index: usize) -> Range<usize> {
+        let bounds = self.word_bounds();
+        let grapheme_index = grapheme_index.min(self.fragments.len().saturating_sub(1));
+        let word = bounds.partition_point(|start| *start <= grapheme_index).saturating_sub(1);
+        match (bounds.get(word), bounds.get(word.saturating_add(1))) {
+            (Some(start), Some(end)) => *start..*end,
+            _ => 0..0,
         }
-        self.fragments = Self::str_to_fragments(&result);
     }
 
-    pub fn delete(&mut self, grapheme_index: usize) {
-        let mut result = String::new();
+/// Where the word after `grapheme_index` ends, or the word it is in;
+/// whitespace before it is skipped. None at the end of the line.
+    pub fn next_word_end(&self, grapheme_index: usize) -> Option<usize> {
+        self.word_bounds()
+            .windows(2)
+            .find(|bounds| bounds[1] > grapheme_index && !self.is_whitespace_at(bounds[0]))
+            .map(|bounds| bounds[1])
+            .or_else(|| (grapheme_index < self.fragments.len()).then_some(self.fragments.len()))
+    }
 
-        for (index, fragment) in self.fragments.iter().enumerate() {
-            if index != grapheme_index {
-                result.push_str(&fragment.grapheme);
-            }
-        }
-        self.fragments = Self::str_to_fragments(&result);
+/// Where the word before `grapheme_index` starts, or the word it is in;
+/// whitespace after it is skipped. None at the start of the line.
+    pub fn previous_word_start(&self, grapheme_index: usize) -> Option<usize> {
+        self.word_bounds()
+            .windows(2)
+            .rev()
+            .find(|bounds| bounds[0] < grapheme_index && !self.is_whitespace_at(bounds[0]))
+            .map(|bounds| bounds[0])
+            .or_else(|| (grapheme_index > 0).then_some(0))
+    }
+
+    fn is_whitespace_at(&self, grapheme_index: usize) -> bool {
+        self.fragments
+            .get(grapheme_index)
+            .is_some_and(|fragment| fragment.grapheme.trim().is_empty())
+    }
+
+    // Where the line is split at Unicode word boundaries, as grapheme
+    // indices from its start to its end.
+    fn word_bounds(&self) -> Vec<usize> {
+        let mut bounds: Vec<usize> = self
+            .string
+            .split_word_bound_indices()
+            .filter_map(|(byte_index, _)| self.byte_index_to_grapheme_index(byte_index))
+            .chain(std::iter::once(self.fragments.len()))
+            .collect();
+        bounds.dedup();
+        bounds
+    }
+
+    pub fn grapheme_count(&self) -> usize {
+        self.fragments.len()
     }
 
-    pub fn append(&mut self, other: &Self) {
-        let mut concat = self.to_string();
-        concat.push_str(&other.to_string());
-        self.fragments = Self::str_to_fragments(&concat);
+/// The graphemes that the byte range `bytes` touches, including ones it
+/// only partly covers.
+    pub fn grapheme_range(&self, bytes: Range<usize>) -> Range<usize> {
+        let start = self
+            .fragments
+            .partition_point(|fragment| fragment.start_byte_index <= bytes.start)
+            .saturating_sub(1);
+        let end = self
+            .fragments
+            .partition_point(|fragment| fragment.start_byte_index < bytes.end);
+        start..end
     }
 
-    pub fn split(&mut self, grapheme_index: usize) -> Self {
-        if grapheme_index >= self.fragments.len() {
-            return Self::default();
+    // Maps a byte index to the grapheme starting there. The end of the line
+    // maps to `grapheme_count`; bytes inside a grapheme map to nothing.
+    fn byte_index_to_grapheme_index(&self, byte_index: usize) -> Option<usize> {
+        if byte_index == self.string.len() {
+            return Some(self.fragments.len());
         }
-        Self { fragments: self.fragments.split_off(grapheme_index) }
+        self.fragments
+            .binary_search_by_key(&byte_index, |fragment| fragment.start_byte_index)
+            .ok()
+    }
+
+/// Finds every match of `pattern` that starts and ends on grapheme
+/// boundaries, as grapheme index ranges.
+///
+/// Matches that would split a grapheme (e.g. `e` inside `é` written as
+/// `e` plus a combining accent) are skipped.
+    pub fn find_all(&self, pattern: &Pattern) -> Vec<Range<usize>> {
+        pattern
+            .find_all(&self.string)
+            .into_iter()
+            .filter_map(|found| {
+                let start = self.byte_index_to_grapheme_index(found.start)?;
+                let end = self.byte_index_to_grapheme_index(found.end)?;
+                Some(start..end)
+            })
+            .collect()
+    }
+
+/// Returns the grapheme index of the first match at or after `from_grapheme_index`.
+    pub fn search_forward(&self, pattern: &Pattern, from_grapheme_index: usize) -> Option<usize> {
+        self.find_all(pattern)
+            .into_iter()
+            .map(|found| found.start)
+            .find(|start| *start >= from_grapheme_index)
+    }
+
+/// Returns the grapheme index of the last match before `before_grapheme_index`.
+    pub fn search_backward(&self, pattern: &Pattern, before_grapheme_index: usize) -> Option<usize> {
+        self.find_all(pattern)
+            .into_iter()
+            .map(|found| found.start)
+            .rfind(|start| *start < before_grapheme_index)
     }
 }
 
 impl fmt::Display for Line {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        let result: String = self
-            .fragments
-            .iter()
-            .map(|fragment| fragment.grapheme.clone())
-            .collect();
-        write!(formatter, "{result}")
+        write!(formatter, "{}", self.string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wrap_starts(text: &str, width: usize) -> Vec<usize> {
+        Line::from(text).wrap_starts(width)
+    }
+
+    #[test]
+    fn rows_break_before_the_word_that_does_not_fit() {
+        assert_eq!(wrap_starts("hello world foo", 8), [0, 6, 12]);
+        assert_eq!(wrap_starts("hello world foo", 20), [0]);
+        assert_eq!(wrap_starts("", 8), [0]);
+    }
+
+    #[test]
+    fn words_wider_than_a_row_are_split() {
+        assert_eq!(wrap_starts("abcdefghij", 4), [0, 4, 8]);
+        assert_eq!(wrap_starts("日本語", 4), [0, 2]);
+        assert_eq!(wrap_starts("日本語", 1), [0, 1, 2, 3]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn whitespace_hangs_past_the_edge() {
+        assert_eq!(wrap_starts("ab   cd", 3), [0, 5]);
+        // The cursor after hanging whitespace goes on a row of its own.
+        assert_eq!(wrap_starts("ab      ", 3), [0, 8]);
+    }
+
+    #[test]
+    fn a_full_last_row_gets_an_empty_row_after_it() {
+        assert_eq!(wrap_starts("abcd", 4), [0, 4]);
+        assert_eq!(wrap_starts("ab cde", 3), [0, 3, 6]);
+        assert_eq!(wrap_starts("ab cd", 3), [0, 3]);
+    }
+
+    #[test]
+    fn columns_map_back_to_graphemes() {
+        let line = Line::from("a日b");
+        assert_eq!(line.width_until(2), 3);
+        assert_eq!(line.grapheme_at_column(0..3, 0), 0);
+        assert_eq!(line.grapheme_at_column(0..3, 2), 1);
+        assert_eq!(line.grapheme_at_column(0..3, 3), 2);
+        assert_eq!(line.grapheme_at_column(1..3, 2), 2);
+        assert_eq!(line.grapheme_at_column(0..3, 9), 3);
+    }
+}
@@ -0,0 +1,70 @@
+/// The line break style of a file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+/// The style of every line break in `text`, in order.
+    pub fn of_each_line(text: &str) -> Vec<Self> {
+        text.match_indices('\n')
+            .map(|(index, _)| if text[..index].ends_with('\r') { Self::CrLf } else { Self::Lf })
+            .collect()
+    }
+
+/// The style used by most of `line_endings`, `CrLf` on a tie.
+///
+/// Text without any line break is treated as `Lf`.
+    pub fn majority(line_endings: &[Self]) -> Self {
+        let line_breaks = line_endings.len();
+        let crlf = line_endings.iter().filter(|line_ending| **line_ending == Self::CrLf).count();
+        if crlf > 0 && crlf.saturating_mul(2) >= line_breaks {
+            Self::CrLf
+        } else {
+            Self::Lf
+        }
+    }
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Lf => "LF",
+            Self::CrLf => "CRLF",
+        }
+    }
+
+    pub const fn toggled(self) -> Self {
+        match self {
+            Self::Lf => Self::CrLf,
+            Self::CrLf => Self::Lf,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_style_of_each_line_break() {
+        assert_eq!(LineEnding::of_each_line("a\r\nb\nc\r\n"), [LineEnding::CrLf, LineEnding::Lf, LineEnding::CrLf]);
+        assert!(LineEnding::of_each_line("no break").is_empty());
+    }
+
+    #[test]
+    fn most_line_breaks_decide_and_ties_go_to_crlf() {
+        let endings = LineEnding::of_each_line("a\r\nb\nc\r\n");
+        assert_eq!(LineEnding::majority(&endings), LineEnding::CrLf);
+        assert_eq!(LineEnding::majority(&endings[1..]), LineEnding::CrLf);
+        assert_eq!(LineEnding::majority(&endings[1..2]), LineEnding::Lf);
+        assert_eq!(LineEnding::majority(&[]), LineEnding::Lf);
+    }
+}
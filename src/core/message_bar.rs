@@ -0,0 +1,84 @@
+use std::time::{Duration, Instant};
+
+use crate::core::frame::Frame;
+use crate::core::terminal::Size;
+use crate::core::theme::Theme;
+
+const DEFAULT_DURATION: Duration = Duration::new(5, 0);
+
+struct Message {
+    text: String,
+    time: Instant,
+}
+
+impl Default for Message {
+    fn default() -> Self {
+        Self { text: String::new(), time: Instant::now() }
+    }
+}
+
+impl Message {
+    fn is_expired(&self) -> bool {
+        Instant::now().duration_since(self.time) > DEFAULT_DURATION
+    }
+}
+
+/// A one-row area at the bottom of the screen for short-lived messages.
+///
+/// A message disappears on its own five seconds after it was set.
+pub struct MessageBar {
+    current_message: Message,
+    need_redraw: bool,
+    cleared_after_expiry: bool,
+    width: usize,
+}
+
+impl Default for MessageBar {
+    fn default() -> Self {
+        Self {
+            current_message: Message::default(),
+            need_redraw: true,
+            cleared_after_expiry: true,
+            width: 0,
+        }
+    }
+}
+
+impl MessageBar {
+    pub fn update_message(&mut self, new_message: &str) {
+        self.current_message = Message {
+            text: new_message.to_string(),
+            time: Instant::now(),
+        };
+        self.cleared_after_expiry = false;
+        self.need_redraw = true;
+    }
+
+    pub fn resize(&mut self, size: Size) {
+        self.width = size.width;
+        self.need_redraw = true;
+    }
+
+/// Forces a redraw, e.g. after something else was drawn over the bar
+/// or the theme changed.
+    pub fn mark_redraw(&mut self) {
+        self.need_redraw = true;
+    }
+
+    pub fn render(&mut self, frame: &mut Frame, row: usize, theme: &Theme) {
+        if self.current_message.is_expired() && !self.cleared_after_expiry {
+            self.cleared_after_expiry = true;
+            self.need_redraw = true;
+        }
+        if !self.need_redraw {
+            return ;
+        }
+        let text: String = if self.current_message.is_expired() {
+            String::new()
+        } else {
+            self.current_message.text.chars().take(self.width).collect()
+        };
+        frame.set_line(row, &text, theme.style("ui.text"));
+        self.need_redraw = false;
+    }
+}
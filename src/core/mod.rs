@@ -4,15 +4,47 @@ mod view;
 mod buffer;
 mod command;
 mod line;
+mod history;
+mod undo_file;
+mod line_ending;
+mod encoding;
+mod atomic_write;
+mod file_type;
+mod status_bar;
+mod message_bar;
+mod prompt;
+mod annotated_string;
+mod pattern;
+mod project_search;
+mod highlighter;
+mod theme;
+mod frame;
+mod backend;
+mod test_backend;
+mod script;
+mod clipboard;
+#[cfg(feature = "tree-sitter")]
+mod syntax_tree;
 
 pub use terminal::Terminal;
 pub use terminal::Position;
-// pub use terminal::Size;
+pub use terminal::Size;
 
 // pub use cursor::Cursor;
 
 pub use view::View;
 pub use view::Location;
 
-pub use command::EditorCommand;
+pub use command::{ EditorCommand, Direction };
+pub use status_bar::StatusBar;
+pub use message_bar::MessageBar;
+pub use prompt::{ Prompt, PromptEvent };
+pub use pattern::Pattern;
+pub use project_search::ProjectSearch;
+pub use theme::{ ColorSupport, Theme };
+pub use frame::{ Cell, Frame };
+pub use backend::Backend;
+pub use test_backend::TestBackend;
+pub use script::Script;
+pub use clipboard::{ Clipboard, ClipboardCommand, DEFAULT_REGISTER };
 // pub use buffer::Buffer;
\ No newline at end of file
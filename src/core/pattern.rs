@@ -0,0 +1,83 @@
+use std::ops::Range;
+
+use regex::Regex;
+
+/// What a search or replace looks for: either literal text or a regular expression.
+#[derive(Clone)]
+pub enum Pattern {
+    Literal(String),
+    Regex(Regex),
+}
+
+impl Pattern {
+    pub fn new(query: &str, is_regex: bool) -> Result<Self, regex::Error> {
+        if is_regex {
+            Ok(Self::Regex(Regex::new(query)?))
+        } else {
+            Ok(Self::Literal(query.to_string()))
+        }
+    }
+
+/// Byte ranges of all non-overlapping, non-empty matches in `haystack`.
+    pub fn find_all(&self, haystack: &str) -> Vec<Range<usize>> {
+        match self {
+            Self::Literal(query) if query.is_empty() => Vec::new(),
+            Self::Literal(query) => haystack
+                .match_indices(query.as_str())
+                .map(|(start, matched)| start..start.saturating_add(matched.len()))
+                .collect(),
+            Self::Regex(regex) => regex
+                .find_iter(haystack)
+                .map(|found| found.range())
+                .filter(|found| !found.is_empty())
+                .collect(),
+        }
+    }
+
+/// The first non-empty match starting at or after byte `start`.
+    pub fn find_at(&self, haystack: &str, start: usize) -> Option<Range<usize>> {
+        match self {
+            Self::Literal(query) if query.is_empty() => None,
+            Self::Literal(query) => haystack
+                .get(start..)?
+                .find(query.as_str())
+                .map(|offset| {
+                    let found = start.saturating_add(offset);
+                    found..found.saturating_add(query.len())
+                }),
+            Self::Regex(regex) => {
+                let mut start = start;
+                while start <= haystack.len() {
+                    let found = regex.find_at(haystack, start)?.range();
+                    if !found.is_empty() {
+                        return Some(found);
+                    }
+                    // Step over the empty match to the next char boundary.
+                    start = haystack[found.end..]
+                        .chars()
+                        .next()
+                        .map_or(usize::MAX, |next| found.end.saturating_add(next.len_utf8()));
+                }
+                None
+            }
+        }
+    }
+
+/// The text that replaces the match `found`.
+///
+/// For regular expressions `$1`, `${name}` and `$$` in `replacement`
+/// refer to capture groups and a literal `$`; literal patterns insert
+/// `replacement` as is.
+    pub fn expand(&self, haystack: &str, found: &Range<usize>, replacement: &str) -> String {
+        match self {
+            Self::Literal(_) => replacement.to_string(),
+            Self::Regex(regex) => {
+                let mut expanded = String::new();
+                if let Some(captures) = regex.captures_at(haystack, found.start) {
+                    captures.expand(replacement, &mut expanded);
+                }
+                expanded
+            }
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+
+use ignore::WalkBuilder;
+
+use crate::core::Location;
+use crate::core::command::Direction;
+use crate::core::encoding::Encoding;
+use crate::core::line::Line;
+use crate::core::pattern::Pattern;
+use crate::core::frame::Frame;
+use crate::core::terminal::Size;
+use crate::core::theme::Theme;
+
+// Stop collecting once this many lines matched, so a too broad query
+// cannot eat up memory.
+const MAX_MATCHES: usize = 10_000;
+
+// How much of a matching line is kept for the results list.
+const MAX_PREVIEW_CHARS: usize = 256;
+
+// How far into a file to look for NUL bytes when deciding whether it is binary.
+const BINARY_CHECK_BYTES: usize = 8192;
+
+/// A line in some file that matches the project search.
+pub struct ProjectMatch {
+    pub file_name: String,
+    pub location: Location,
+    preview: String,
+}
+
+// What the background search sends back.
+enum SearchUpdate {
+    Matches(Vec<ProjectMatch>),
+    Done { files_searched: usize },
+}
+
+/// A search through every file under a directory, with its results list.
+///
+/// The walk runs on a background thread that skips ignored (e.g.
+/// `.gitignore`d), hidden and binary files. Results arrive through a
+/// channel that `poll` drains, so the editor stays responsive while a
+/// large tree is searched. Dropping the search stops the thread.
+pub struct ProjectSearch {
+    query: String,
+    receiver: Receiver<SearchUpdate>,
+    cancelled: Arc<AtomicBool>,
+    matches: Vec<ProjectMatch>,
+    // Set once the walk has finished.
+    files_searched: Option<usize>,
+    selected: usize,
+    scroll_offset: usize,
+    size: Size,
+    need_redraw: bool,
+}
+
+impl ProjectSearch {
+/// Starts searching the files under `root` for `pattern` in the background.
+    pub fn start(root: PathBuf, query: &str, pattern: Pattern, size: Size) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let worker_cancelled = Arc::clone(&cancelled);
+        thread::spawn(move || Self::search_tree(&root, &pattern, &sender, &worker_cancelled));
+        Self {
+            query: query.to_string(),
+            receiver,
+            cancelled,
+            matches: Vec::new(),
+            files_searched: None,
+            selected: 0,
+            scroll_offset: 0,
+            size,
+            need_redraw: true,
+        }
+    }
+
+    fn search_tree(root: &Path, pattern: &Pattern, sender: &Sender<SearchUpdate>, cancelled: &AtomicBool) {
+        let mut files_searched: usize = 0;
+        let mut match_count: usize = 0;
+        // Honour .gitignore files even outside of a git checkout.
+        let walker = WalkBuilder::new(root).require_git(false).build();
+        for entry in walker.flatten() {
+            if cancelled.load(Ordering::Relaxed) || match_count >= MAX_MATCHES {
+                break;
+            }
+            if !entry.file_type().is_some_and(|file_type| file_type.is_file()) {
+                continue;
+            }
+            let Some(matches) = Self::search_file(root, entry.path(), pattern) else {
+                continue;
+            };
+            files_searched = files_searched.saturating_add(1);
+            if matches.is_empty() {
+                continue;
+            }
+            match_count = match_count.saturating_add(matches.len());
+            if sender.send(SearchUpdate::Matches(matches)).is_err() {
+                return ;
+            }
+        }
+        let _ = sender.send(SearchUpdate::Done { files_searched });
+    }
+
+    // The matching lines of one file, or `None` if it is unreadable or binary.
+    fn search_file(root: &Path, path: &Path, pattern: &Pattern) -> Option<Vec<ProjectMatch>> {
+        let bytes = fs::read(path).ok()?;
+        let encoding = Encoding::detect(&bytes);
+        let is_utf16 = matches!(encoding, Encoding::Utf16Le | Encoding::Utf16Be);
+        let head = &bytes[..bytes.len().min(BINARY_CHECK_BYTES)];
+        if !is_utf16 && head.contains(&0) {
+            return None;
+        }
+        let text = encoding.decode(&bytes);
+        let file_name = path.strip_prefix(root).unwrap_or(path).to_string_lossy().into_owned();
+
+        // Lines are numbered like buffer lines: only `\n` ends one, and a
+        // `\r` right before it belongs to the line ending.
+        let matches = text
+            .split_terminator('\n')
+            .map(|line| line.strip_suffix('\r').unwrap_or(line))
+            .enumerate()
+            .filter(|(_, line)| pattern.find_at(line, 0).is_some())
+            .filter_map(|(line_index, line)| {
+                let grapheme_index = Line::from(line).search_forward(pattern, 0)?;
+                Some(ProjectMatch {
+                    file_name: file_name.clone(),
+                    location: Location { line_index, grapheme_index },
+                    preview: line.trim().chars().take(MAX_PREVIEW_CHARS).collect(),
+                })
+            })
+            .collect();
+        Some(matches)
+    }
+
+/// Takes in whatever the background search found since the last call.
+    pub fn poll(&mut self) {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(SearchUpdate::Matches(mut matches)) => {
+                    self.matches.append(&mut matches);
+                    self.need_redraw = true;
+                }
+                Ok(SearchUpdate::Done { files_searched }) => {
+                    self.files_searched = Some(files_searched);
+                    self.need_redraw = true;
+                }
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => return ,
+            }
+        }
+    }
+
+    pub const fn is_running(&self) -> bool {
+        self.files_searched.is_none()
+    }
+
+    pub fn selected_match(&self) -> Option<&ProjectMatch> {
+        self.matches.get(self.selected)
+    }
+
+    pub fn resize(&mut self, size: Size) {
+        self.size = size;
+        self.scroll_selection_into_view();
+        self.need_redraw = true;
+    }
+
+    pub fn move_selection(&mut self, direction: &Direction) {
+        let page = self.list_height().max(1);
+        let last = self.matches.len().saturating_sub(1);
+        self.selected = match direction {
+            Direction::Up | Direction::Left => self.selected.saturating_sub(1),
+            Direction::Down | Direction::Right => self.selected.saturating_add(1).min(last),
+            Direction::PageUp => self.selected.saturating_sub(page),
+            Direction::PageDown => self.selected.saturating_add(page).min(last),
+            Direction::Home | Direction::DocumentStart => 0,
+            Direction::End | Direction::DocumentEnd => last,
+            Direction::WordLeft | Direction::WordRight => self.selected,
+        };
+        self.scroll_selection_into_view();
+        self.need_redraw = true;
+    }
+
+    // The first row holds the summary; the rest list the matches.
+    const fn list_height(&self) -> usize {
+        self.size.height.saturating_sub(1)
+    }
+
+    fn scroll_selection_into_view(&mut self) {
+        let height = self.list_height();
+        if self.selected < self.scroll_offset {
+            self.scroll_offset = self.selected;
+        } else if self.selected >= self.scroll_offset.saturating_add(height) {
+            self.scroll_offset = self.selected.saturating_sub(height).saturating_add(1);
+        }
+    }
+
+    fn summary(&self) -> String {
+        let found = match self.matches.len() {
+            0 if !self.is_running() => format!("No matches for \"{}\"", self.query),
+            count => format!("{count} matching lines for \"{}\"", self.query),
+        };
+        match self.files_searched {
+            None => format!("{found} (searching...)"),
+            Some(files) if self.matches.len() >= MAX_MATCHES => {
+                format!("{found} in {files} files (stopped at {MAX_MATCHES})")
+            }
+            Some(files) => format!("{found} in {files} files - Enter to open, Esc to close"),
+        }
+    }
+
+/// Forces a redraw, e.g. after the theme changed.
+    pub fn mark_redraw(&mut self) {
+        self.need_redraw = true;
+    }
+
+/// Draws the results over the text area, highlighting the selected one.
+    pub fn render(&mut self, frame: &mut Frame, theme: &Theme) {
+        if !self.need_redraw {
+            return ;
+        }
+        let Size { height, width } = self.size;
+        let summary: String = self.summary().chars().take(width).collect();
+        frame.set_line(0, &summary, theme.style("ui.statusbar"));
+        for row in 1..height {
+            let index = self.scroll_offset.saturating_add(row).saturating_sub(1);
+            let line: String = self.matches.get(index).map_or_else(String::new, |found| {
+                format!(
+                    "{}:{}: {}",
+                    found.file_name,
+                    found.location.line_index.saturating_add(1),
+                    found.preview,
+                )
+                .chars()
+                .map(|character| if character == '\t' { ' ' } else { character })
+                .take(width)
+                .collect()
+            });
+            let style = if index == self.selected && index < self.matches.len() {
+                theme.style("ui.selection").over(theme.style("ui.text"))
+            } else {
+                theme.style("ui.text")
+            };
+            frame.set_line(row, &line, style);
+        }
+        self.need_redraw = false;
+    }
+}
+
+impl Drop for ProjectSearch {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(name: &str, files: &[(&str, &[u8])]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("zen_quill_project_{}_{name}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        for (file_name, contents) in files {
+            fs::write(root.join(file_name), contents).unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn binary_and_ignored_files_are_skipped() {
+        let root = project("skipped", &[
+            ("found.txt", b"needle\n"),
+            ("binary.dat", b"needle\0\n"),
+            ("ignored.txt", b"needle\n"),
+            (".gitignore", b"ignored.txt\n"),
+        ]);
+        let (sender, receiver) = mpsc::channel();
+        ProjectSearch::search_tree(&root, &Pattern::new("needle", false).unwrap(), &sender, &AtomicBool::new(false));
+        drop(sender);
+        let mut file_names = Vec::new();
+        let mut files_searched = None;
+        for update in receiver {
+            match update {
+                SearchUpdate::Matches(matches) => file_names.extend(matches.into_iter().map(|found| found.file_name)),
+                SearchUpdate::Done { files_searched: files } => files_searched = Some(files),
+            }
+        }
+        assert_eq!(file_names, ["found.txt"]);
+        assert_eq!(files_searched, Some(1));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn locations_are_numbered_like_buffer_lines() {
+        let root = project("locations", &[("file.txt", "a\x0cneedle\r\nx\u{2028}y\rz\n  \u{e9}needle\n".as_bytes())]);
+        let pattern = Pattern::new("needle", false).unwrap();
+        let matches = ProjectSearch::search_file(&root, &root.join("file.txt"), &pattern).unwrap();
+        let found: Vec<_> = matches
+            .iter()
+            .map(|found| (found.location.line_index, found.location.grapheme_index, found.preview.as_str()))
+            .collect();
+        assert_eq!(found, [(0, 2, "a\x0cneedle"), (2, 3, "\u{e9}needle")]);
+        fs::remove_dir_all(root).unwrap();
+    }
+}
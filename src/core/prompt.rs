@@ -0,0 +1,197 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::core::command::{Direction, EditorCommand};
+use crate::core::frame::Frame;
+use crate::core::terminal::Size;
+use crate::core::theme::Theme;
+
+/// What a prompt did with a command.
+pub enum PromptEvent {
+    /// The input text changed.
+    Edited,
+    /// The command only moved the caret or was ignored.
+    Unchanged,
+    Submitted(String),
+    Cancelled,
+}
+
+/// A single-line input field shown in place of the message bar.
+///
+/// Supports caret movement and editing, walking back through earlier
+/// entries with Up/Down, submitting with Enter and cancelling with Escape.
+pub struct Prompt {
+    label: String,
+    value: String,
+    // Caret position, as a grapheme index into `value`.
+    caret: usize,
+    history: Vec<String>,
+    // Which history entry is shown, if any. The text typed before browsing
+    // the history is kept in `draft`.
+    history_index: Option<usize>,
+    draft: String,
+    width: usize,
+}
+
+impl Prompt {
+    pub fn new(label: &str, history: Vec<String>, size: Size) -> Self {
+        Self {
+            label: label.to_string(),
+            value: String::new(),
+            caret: 0,
+            history,
+            history_index: None,
+            draft: String::new(),
+            width: size.width,
+        }
+    }
+
+    pub fn set_label(&mut self, label: &str) {
+        self.label = label.to_string();
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn resize(&mut self, size: Size) {
+        self.width = size.width;
+    }
+
+    pub fn handle_command(&mut self, command: &EditorCommand) -> PromptEvent {
+        match command {
+            EditorCommand::Insert(character) => {
+                self.insert(*character);
+                PromptEvent::Edited
+            }
+            EditorCommand::Backspace if self.caret > 0 => {
+                self.caret -= 1;
+                self.remove_at_caret();
+                PromptEvent::Edited
+            }
+            EditorCommand::Delete if self.caret < self.grapheme_count() => {
+                self.remove_at_caret();
+                PromptEvent::Edited
+            }
+            EditorCommand::Move(Direction::Left) => {
+                self.caret = self.caret.saturating_sub(1);
+                PromptEvent::Unchanged
+            }
+            EditorCommand::Move(Direction::Right) => {
+                self.caret = self.caret.saturating_add(1).min(self.grapheme_count());
+                PromptEvent::Unchanged
+            }
+            EditorCommand::Move(Direction::Home) => {
+                self.caret = 0;
+                PromptEvent::Unchanged
+            }
+            EditorCommand::Move(Direction::End) => {
+                self.caret = self.grapheme_count();
+                PromptEvent::Unchanged
+            }
+            EditorCommand::Move(Direction::Up) => self.history_previous(),
+            EditorCommand::Move(Direction::Down) => self.history_next(),
+            EditorCommand::Enter => PromptEvent::Submitted(self.value.clone()),
+            EditorCommand::Dismiss => PromptEvent::Cancelled,
+            _ => PromptEvent::Unchanged,
+        }
+    }
+
+    fn grapheme_count(&self) -> usize {
+        self.value.graphemes(true).count()
+    }
+
+    // Byte offset of the grapheme at `grapheme_index`, or the end of the value.
+    fn byte_offset(&self, grapheme_index: usize) -> usize {
+        self.value
+            .grapheme_indices(true)
+            .nth(grapheme_index)
+            .map_or(self.value.len(), |(offset, _)| offset)
+    }
+
+    fn insert(&mut self, character: char) {
+        let old_count = self.grapheme_count();
+        let offset = self.byte_offset(self.caret);
+        self.value.insert(offset, character);
+        if self.grapheme_count() > old_count {
+            self.caret = self.caret.saturating_add(1);
+        }
+    }
+
+    fn remove_at_caret(&mut self) {
+        let start = self.byte_offset(self.caret);
+        let end = self.byte_offset(self.caret.saturating_add(1));
+        self.value.replace_range(start..end, "");
+    }
+
+    fn history_previous(&mut self) -> PromptEvent {
+        let index = match self.history_index {
+            None if self.history.is_empty() => return PromptEvent::Unchanged,
+            None => {
+                self.draft = self.value.clone();
+                self.history.len().saturating_sub(1)
+            }
+            Some(index) => index.saturating_sub(1),
+        };
+        self.show_history_entry(Some(index))
+    }
+
+    fn history_next(&mut self) -> PromptEvent {
+        match self.history_index {
+            None => PromptEvent::Unchanged,
+            Some(index) if index.saturating_add(1) >= self.history.len() => {
+                self.show_history_entry(None)
+            }
+            Some(index) => self.show_history_entry(Some(index.saturating_add(1))),
+        }
+    }
+
+    fn show_history_entry(&mut self, index: Option<usize>) -> PromptEvent {
+        self.history_index = index;
+        self.value = index
+            .and_then(|index| self.history.get(index))
+            .map_or_else(|| self.draft.clone(), Clone::clone);
+        self.caret = self.grapheme_count();
+        PromptEvent::Edited
+    }
+
+    // How many columns of the value are scrolled out on the left so the
+    // caret stays visible.
+    fn scroll_offset(&self) -> usize {
+        let label_width = self.label.width();
+        let caret_width = self.value[..self.byte_offset(self.caret)].width();
+        let available = self.width.saturating_sub(label_width).saturating_sub(1);
+        caret_width.saturating_sub(available)
+    }
+
+/// The screen column the caret belongs in.
+    pub fn caret_column(&self) -> usize {
+        let caret_width = self.value[..self.byte_offset(self.caret)].width();
+        self.label
+            .width()
+            .saturating_add(caret_width)
+            .saturating_sub(self.scroll_offset())
+    }
+
+    pub fn render(&self, frame: &mut Frame, row: usize, theme: &Theme) {
+        let mut skipped = 0;
+        let scroll_offset = self.scroll_offset();
+        let visible: String = self
+            .value
+            .graphemes(true)
+            .skip_while(|grapheme| {
+                let skip = skipped < scroll_offset;
+                skipped = skipped.saturating_add(grapheme.width());
+                skip
+            })
+            .collect();
+        let line: String = format!("{}{visible}", self.label)
+            .graphemes(true)
+            .scan(0_usize, |width, grapheme| {
+                *width = width.saturating_add(grapheme.width());
+                (*width <= self.width).then_some(grapheme)
+            })
+            .collect();
+        frame.set_line(row, &line, theme.style("ui.text"));
+    }
+}
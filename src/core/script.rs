@@ -0,0 +1,220 @@
+use std::error::Error;
+use std::fs;
+use std::io::{ Read, stdin };
+use std::path::Path;
+
+use crossterm::event::{ Event, KeyCode, KeyEvent, KeyModifiers };
+
+use crate::core::clipboard::{ Clipboard, DEFAULT_REGISTER };
+use crate::core::command::EditorCommand;
+use crate::core::terminal::Size;
+use crate::core::view::View;
+
+// The window size commands like PageDown act as if they had.
+const SCRIPT_SIZE: Size = Size { height: 24, width: 80 };
+
+// One thing a script line asks for.
+enum Step {
+    Command(EditorCommand),
+    GoToLine(usize),
+}
+
+/// Edits a file without a terminal, by applying a script of editor
+/// commands and writing the result.
+///
+/// Started with `zen_quill --script <script> [--output <file>] <file>`,
+/// where the script may be `-` for standard input. Without `--output` the
+/// file is changed in place; a file that does not exist yet starts empty.
+/// Scripts leave no undo history behind.
+///
+/// Each line of the script is one of:
+///
+/// - `type <text>` inserts the rest of the line as is,
+/// - `goto <line>` moves to the start of a line,
+/// - a named command: `undo`, `redo`, `save`, `toggle-line-ending`,
+///   `cycle-encoding` or `reopen-with-encoding`,
+/// - a key, like `enter`, `shift+end`, `ctrl+z` or `alt+w`, which does what
+///   pressing it does in the editor.
+///
+/// Blank lines and lines starting with `#` are skipped. Commands go
+/// through the same `View::handle_command` as key presses do, so a script
+/// edits exactly like a person would. Keys that open prompts or switch
+/// the theme are refused, as is `save` together with `--output`, and cut,
+/// copy and paste keep to a clipboard of the script's own. Soft wrap and
+/// line numbers may be toggled, since they change where moving up and down
+/// lands.
+pub struct Script {
+    file_name: String,
+    source: String,
+    output: Option<String>,
+}
+
+impl Script {
+/// The script asked for by the command line `args`, if any.
+    pub fn from_args(args: &[String]) -> Result<Option<Self>, Box<dyn Error>> {
+        let mut args = args.iter();
+        let (mut script, mut output, mut file_name) = (None, None, None);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--script" => script = Some(args.next().ok_or("--script needs a file, or - for standard input")?),
+                "--output" => output = Some(args.next().ok_or("--output needs a file")?.clone()),
+                "--wrap-column" | "--clipboard" | "--undo-dir" => {
+                    args.next();
+                }
+                _ if file_name.is_none() => file_name = Some(arg.clone()),
+                _ => return Err(format!("unexpected argument {arg}").into()),
+            }
+        }
+        let Some(script) = script else {
+            return Ok(None);
+        };
+        let file_name = file_name.ok_or("a script needs a file to edit")?;
+        let source = if script == "-" {
+            let mut source = String::new();
+            stdin().read_to_string(&mut source)?;
+            source
+        } else {
+            fs::read_to_string(script).map_err(|err| format!("could not read {script}: {err}"))?
+        };
+        Ok(Some(Self { file_name, source, output }))
+    }
+
+/// Applies the script to the file and writes the result.
+///
+/// An invalid line stops the script before anything is written. A line
+/// that fails while running stops it too, but what an earlier `save`
+/// wrote stays on disk.
+    pub fn run(&self) -> Result<(), Box<dyn Error>> {
+        let steps = Self::parse(&self.source)?;
+        if self.output.is_some()
+            && let Some((line_number, _)) = steps.iter().find(|(_, step)| matches!(step, Step::Command(EditorCommand::Save)))
+        {
+            return Err(format!("line {line_number}: save would write to {} rather than the output", self.file_name).into());
+        }
+        let mut view = View::default();
+        view.set_undo_dir(None);
+        view.handle_command(EditorCommand::Resize(SCRIPT_SIZE))?;
+        if Path::new(&self.file_name).exists() {
+            view.load(&self.file_name)
+                .map_err(|err| format!("could not open {}: {err}", self.file_name))?;
+        }
+        let mut clipboard = Clipboard::default();
+        for (line_number, step) in steps {
+            match step {
+                Step::Command(EditorCommand::Cut) => {
+                    if let Some(text) = view.cut_selection() {
+                        clipboard.copy(DEFAULT_REGISTER, &text)?;
+                    }
+                }
+                Step::Command(EditorCommand::Copy) => {
+                    if let Some(text) = view.selected_text() {
+                        clipboard.copy(DEFAULT_REGISTER, &text)?;
+                    }
+                }
+                Step::Command(EditorCommand::Paste) => {
+                    let text = clipboard
+                        .paste(DEFAULT_REGISTER)?
+                        .ok_or_else(|| format!("line {line_number}: nothing was cut or copied to paste"))?;
+                    view.paste(&text);
+                }
+                Step::Command(command) => view
+                    .handle_command(command)
+                    .map_err(|err| format!("line {line_number}: {err}"))?,
+                Step::GoToLine(line) => view.go_to_line(line),
+            }
+        }
+        view.save_as(self.output.as_deref().unwrap_or(&self.file_name))
+    }
+
+    // Every step of the script with the line it comes from.
+    fn parse(source: &str) -> Result<Vec<(usize, Step)>, Box<dyn Error>> {
+        let mut steps = Vec::new();
+        for (line_index, line) in source.lines().enumerate() {
+            let line_number = line_index.saturating_add(1);
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            if let Some(text) = line.trim_start().strip_prefix("type ") {
+                steps.extend(text.chars().map(|character| (line_number, Step::Command(EditorCommand::Insert(character)))));
+            } else if let Some(line) = trimmed.strip_prefix("goto ") {
+                let line = line.trim().parse().map_err(|_| format!("line {line_number}: {line} is not a line number"))?;
+                steps.push((line_number, Step::GoToLine(line)));
+            } else {
+                let command = Self::parse_command(trimmed)
+                    .ok_or_else(|| format!("line {line_number}: unknown command {trimmed}"))?;
+                if !Self::is_scriptable(&command) {
+                    return Err(format!("line {line_number}: {trimmed} only works in the interactive editor").into());
+                }
+                steps.push((line_number, Step::Command(command)));
+            }
+        }
+        Ok(steps)
+    }
+
+    fn parse_command(name: &str) -> Option<EditorCommand> {
+        let command = match name {
+            "undo" => EditorCommand::Undo,
+            "redo" => EditorCommand::Redo,
+            "save" => EditorCommand::Save,
+            "toggle-line-ending" => EditorCommand::ToggleLineEnding,
+            "cycle-encoding" => EditorCommand::CycleEncoding,
+            "reopen-with-encoding" => EditorCommand::ReopenWithEncoding,
+            key => return EditorCommand::try_from(&Event::Key(Self::parse_key(key)?)).ok(),
+        };
+        Some(command)
+    }
+
+    // A key like `x`, `enter` or `ctrl+shift+left`.
+    fn parse_key(key: &str) -> Option<KeyEvent> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = key;
+        while let Some((modifier, after)) = rest.split_once('+') && !after.is_empty() {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return None,
+            };
+            rest = after;
+        }
+        let mut characters = rest.chars();
+        let code = match (characters.next(), characters.next()) {
+            (Some(character), None) => KeyCode::Char(character),
+            _ => match rest.to_lowercase().as_str() {
+                "enter" => KeyCode::Enter,
+                "tab" => KeyCode::Tab,
+                "esc" => KeyCode::Esc,
+                "backspace" => KeyCode::Backspace,
+                "delete" => KeyCode::Delete,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                _ => return None,
+            },
+        };
+        Some(KeyEvent::new(code, modifiers))
+    }
+
+    // Whether a command does something without prompts and changes more
+    // than the colors.
+    const fn is_scriptable(command: &EditorCommand) -> bool {
+        !matches!(
+            command,
+            EditorCommand::Quit |
+            EditorCommand::Dismiss |
+            EditorCommand::GoToLine |
+            EditorCommand::Search |
+            EditorCommand::Replace |
+            EditorCommand::ToggleRegex |
+            EditorCommand::ProjectSearch |
+            EditorCommand::CycleTheme |
+            EditorCommand::SelectRegister
+        )
+    }
+}
@@ -0,0 +1,122 @@
+use unicode_width::UnicodeWidthStr;
+
+use crate::core::frame::Frame;
+use crate::core::terminal::Size;
+use crate::core::file_type::FileType;
+use crate::core::theme::Theme;
+
+/// What the status bar shows about the current document.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct DocumentStatus {
+    pub file_name: Option<String>,
+    pub is_modified: bool,
+    pub line_count: usize,
+    pub current_line: usize,
+    pub current_column: usize,
+    pub file_type: FileType,
+    pub encoding: &'static str,
+    pub line_ending: &'static str,
+}
+
+impl DocumentStatus {
+    fn left_text(&self) -> String {
+        let file_name = self.file_name.as_deref().unwrap_or("[No Name]");
+        let modified = if self.is_modified { " (modified)" } else { "" };
+        format!("{file_name}{modified} - {} lines", self.line_count)
+    }
+
+    fn right_text(&self) -> String {
+        format!(
+            "{} | {} | {} | {}:{}",
+            self.file_type,
+            self.encoding,
+            self.line_ending,
+            self.current_line,
+            self.current_column,
+        )
+    }
+}
+
+/// A one-row bar below the text area describing the open document.
+pub struct StatusBar {
+    current_status: DocumentStatus,
+    need_redraw: bool,
+    width: usize,
+}
+
+impl Default for StatusBar {
+    fn default() -> Self {
+        Self {
+            current_status: DocumentStatus::default(),
+            need_redraw: true,
+            width: 0,
+        }
+    }
+}
+
+impl StatusBar {
+    pub fn resize(&mut self, size: Size) {
+        self.width = size.width;
+        self.need_redraw = true;
+    }
+
+/// Forces a redraw, e.g. after the theme changed.
+    pub fn mark_redraw(&mut self) {
+        self.need_redraw = true;
+    }
+
+    pub fn update_status(&mut self, new_status: DocumentStatus) {
+        if new_status != self.current_status {
+            self.current_status = new_status;
+            self.need_redraw = true;
+        }
+    }
+
+/// Draws the bar on `row`, with the document name on the left and the
+/// cursor position on the right.
+    pub fn render(&mut self, frame: &mut Frame, row: usize, theme: &Theme) {
+        if !self.need_redraw {
+            return ;
+        }
+        let left = self.current_status.left_text();
+        let right = self.current_status.right_text();
+
+        // Widths are in columns, since file names may hold wide characters.
+        // A line that does not fit is cut off at the edge by the frame.
+        let used = left.width().saturating_add(right.width());
+        let padding = self.width.saturating_sub(used).max(1);
+        let line = format!("{left}{}{right}", " ".repeat(padding));
+
+        frame.set_line(row, &line, theme.style("ui.statusbar"));
+        self.need_redraw = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wide_file_names_keep_the_right_side_at_the_edge() {
+        let size = Size { height: 1, width: 50 };
+        let mut frame = Frame::new(size);
+        let mut status_bar = StatusBar::default();
+        status_bar.resize(size);
+        let status = DocumentStatus {
+            file_name: Some("日本語.txt".to_string()),
+            line_count: 3,
+            current_line: 1,
+            current_column: 1,
+            encoding: "UTF-8",
+            line_ending: "LF",
+            ..DocumentStatus::default()
+        };
+        let right = status.right_text();
+        status_bar.update_status(status);
+        status_bar.render(&mut frame, 0, &Theme::default());
+        let row = frame.row_text(0);
+        assert!(row.starts_with("日本語.txt - 3 lines "));
+        assert!(row.ends_with(&right));
+        assert_eq!(row.width(), 50);
+    }
+}
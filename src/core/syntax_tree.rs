@@ -0,0 +1,248 @@
+use std::collections::HashSet;
+use std::ops::Range;
+
+use ropey::Rope;
+use tree_sitter::{ InputEdit, Node, Parser, Point, Query, QueryCursor, StreamingIterator, Tree };
+
+use crate::core::annotated_string::{ Annotation, AnnotationType };
+use crate::core::file_type::FileType;
+use crate::core::history::Edit;
+use crate::core::line::Line;
+
+/// A tree-sitter syntax tree of a buffer, for languages that have a grammar.
+///
+/// Buffer edits are applied to the tree as they happen and the document is
+/// re-parsed lazily, reusing the unchanged parts of the old tree. The tree
+/// drives highlighting (through the grammar's highlights query) and the
+/// structural navigation commands.
+pub struct SyntaxTree {
+    parser: Parser,
+    query: Query,
+    tree: Option<Tree>,
+    // Whether edits came in since the tree was last parsed.
+    stale: bool,
+    // The lines `annotations` was computed for, while still valid.
+    highlighted: Option<Range<usize>>,
+    annotations: Vec<Vec<Annotation>>,
+}
+
+impl SyntaxTree {
+/// A syntax tree for `file_type`, if there is a grammar for it.
+    pub fn new(file_type: FileType) -> Option<Self> {
+        if file_type != FileType::Rust {
+            return None;
+        }
+        let language = tree_sitter_rust::LANGUAGE.into();
+        let mut parser = Parser::new();
+        parser.set_language(&language).ok()?;
+        let query = Query::new(&language, tree_sitter_rust::HIGHLIGHTS_QUERY).ok()?;
+        Some(Self {
+            parser,
+            query,
+            tree: None,
+            stale: true,
+            highlighted: None,
+            annotations: Vec::new(),
+        })
+    }
+
+/// Tells the tree about an edit that is about to be applied to `text`.
+    pub fn edit(&mut self, text: &Rope, edit: &Edit) {
+        self.stale = true;
+        self.highlighted = None;
+        let Some(tree) = &mut self.tree else {
+            return ;
+        };
+        let start_byte = text.char_to_byte(edit.at().min(text.len_chars()));
+        let start_position = Self::point_at(text, start_byte);
+        let (Edit::Insert { text: edited, .. } | Edit::Delete { text: edited, .. }) = edit;
+        let end_byte = start_byte.saturating_add(edited.len());
+        let end_position = Self::point_after(start_position, edited);
+        let (old_end_byte, new_end_byte, old_end_position, new_end_position) = match edit {
+            Edit::Insert { .. } => (start_byte, end_byte, start_position, end_position),
+            Edit::Delete { .. } => (end_byte, start_byte, end_position, start_position),
+        };
+        tree.edit(&InputEdit {
+            start_byte,
+            old_end_byte,
+            new_end_byte,
+            start_position,
+            old_end_position,
+            new_end_position,
+        });
+    }
+
+    fn point_at(text: &Rope, byte: usize) -> Point {
+        let row = text.byte_to_line(byte);
+        Point { row, column: byte.saturating_sub(text.line_to_byte(row)) }
+    }
+
+    // Where `start` ends up after `inserted` is written there.
+    fn point_after(start: Point, inserted: &str) -> Point {
+        match inserted.rfind('\n') {
+            Some(last_break) => Point {
+                row: start.row.saturating_add(inserted.matches('\n').count()),
+                column: inserted.len().saturating_sub(last_break).saturating_sub(1),
+            },
+            None => Point { row: start.row, column: start.column.saturating_add(inserted.len()) },
+        }
+    }
+
+    // Re-parses the document if it changed since the last parse.
+    fn parse(&mut self, text: &Rope) {
+        if self.stale || self.tree.is_none() {
+            let mut read = |byte: usize, _: Point| -> &[u8] {
+                if byte >= text.len_bytes() {
+                    return &[];
+                }
+                let (chunk, chunk_start, _, _) = text.chunk_at_byte(byte);
+                &chunk.as_bytes()[byte.saturating_sub(chunk_start)..]
+            };
+            self.tree = self.parser.parse_with_options(&mut read, self.tree.as_ref(), None);
+            self.stale = false;
+        }
+    }
+
+/// Highlights the lines in `lines` unless they still are from the last call.
+    pub fn highlight(&mut self, text: &Rope, lines: Range<usize>) {
+        let lines = lines.start..lines.end.min(text.len_lines());
+        if self.highlighted.as_ref() == Some(&lines) && !self.stale {
+            return ;
+        }
+        if lines.is_empty() {
+            self.annotations.clear();
+            self.highlighted = Some(lines);
+            return ;
+        }
+        self.parse(text);
+        let Some(tree) = &self.tree else {
+            return ;
+        };
+        let root = tree.root_node();
+        let line_strings: Vec<String> = lines
+            .clone()
+            .map(|line_index| {
+                let mut line = text.line(line_index).to_string();
+                if line.ends_with('\n') {
+                    line.pop();
+                }
+                line
+            })
+            .collect();
+        let graphemes: Vec<Line> = line_strings.iter().map(|line| Line::from(line)).collect();
+        let mut annotations = vec![Vec::new(); lines.len()];
+
+        let mut cursor = QueryCursor::new();
+        cursor.set_byte_range(text.line_to_byte(lines.start)..text.line_to_byte(lines.end));
+        let node_text = |node: Node| text.byte_slice(node.byte_range()).chunks().map(str::as_bytes);
+        let mut captures = cursor.captures(&self.query, root, node_text);
+        // Like tree-sitter's own highlighter, the first pattern that captures a node wins.
+        let mut captured = HashSet::new();
+        while let Some((found, capture_index)) = captures.next() {
+            let capture = found.captures[*capture_index];
+            let node = capture.node;
+            let name = self.query.capture_names()[capture.index as usize];
+            let Some(annotation_type) = Self::annotation_type(name, node.kind()) else {
+                continue;
+            };
+            if !captured.insert(node.id()) {
+                continue;
+            }
+            let (start, end) = (node.start_position(), node.end_position());
+            for line_index in start.row.max(lines.start)..=end.row.min(lines.end.saturating_sub(1)) {
+                let offset = line_index.saturating_sub(lines.start);
+                let Some(line) = line_strings.get(offset) else {
+                    continue;
+                };
+                let from = if line_index == start.row { start.column } else { 0 };
+                let to = if line_index == end.row { end.column } else { line.len() };
+                let range = graphemes[offset].grapheme_range(from..to);
+                annotations[offset].push(Annotation {
+                    annotation_type,
+                    start: range.start,
+                    end: range.end,
+                });
+            }
+        }
+        self.annotations = annotations;
+        self.highlighted = Some(lines);
+    }
+
+    fn annotation_type(capture_name: &str, node_kind: &str) -> Option<AnnotationType> {
+        match node_kind {
+            "integer_literal" | "float_literal" => return Some(AnnotationType::Number),
+            "char_literal" => return Some(AnnotationType::Character),
+            "lifetime" => return Some(AnnotationType::Lifetime),
+            _ => (),
+        }
+        let annotation_type = match capture_name {
+            "function.macro" => AnnotationType::Macro,
+            "variable.builtin" => AnnotationType::Keyword,
+            "escape" => AnnotationType::Character,
+            "label" => AnnotationType::Lifetime,
+            _ => match capture_name.split('.').next()? {
+                "keyword" => AnnotationType::Keyword,
+                "type" | "constructor" => AnnotationType::Type,
+                "function" => AnnotationType::Function,
+                "string" => AnnotationType::String,
+                "comment" => AnnotationType::Comment,
+                "constant" => AnnotationType::Constant,
+                "attribute" => AnnotationType::Attribute,
+                _ => return None,
+            },
+        };
+        Some(annotation_type)
+    }
+
+/// The annotations of a line, if it was part of the last `highlight`.
+    pub fn annotations(&self, line_index: usize) -> &[Annotation] {
+        self.highlighted
+            .as_ref()
+            .and_then(|lines| self.annotations.get(line_index.checked_sub(lines.start)?))
+            .map_or(&[], Vec::as_slice)
+    }
+
+/// The start of the closest enclosing node that does not start at `byte`.
+    pub fn parent_node(&mut self, text: &Rope, byte: usize) -> Option<usize> {
+        self.parse(text);
+        let root = self.tree.as_ref()?.root_node();
+        let mut node = root.named_descendant_for_byte_range(byte, byte)?;
+        while node.start_byte() == byte {
+            node = node.parent()?;
+        }
+        (node.id() != root.id()).then(|| node.start_byte())
+    }
+
+/// The byte range of the smallest node that contains `selected` and is larger than it.
+    pub fn enclosing_node(&mut self, text: &Rope, selected: Range<usize>) -> Option<Range<usize>> {
+        self.parse(text);
+        let root = self.tree.as_ref()?.root_node();
+        let mut node = root.named_descendant_for_byte_range(selected.start, selected.end)?;
+        while node.byte_range() == selected {
+            node = node.parent()?;
+        }
+        Some(node.byte_range())
+    }
+
+/// The start of the first function that begins after `byte`.
+    pub fn next_function(&mut self, text: &Rope, byte: usize) -> Option<usize> {
+        self.parse(text);
+        let root = self.tree.as_ref()?.root_node();
+        let mut cursor = root.walk();
+        loop {
+            let node = cursor.node();
+            if node.start_byte() > byte && node.kind() == "function_item" {
+                return Some(node.start_byte());
+            }
+            // Only descend into nodes that may hold a later function.
+            if node.end_byte() > byte && cursor.goto_first_child() {
+                continue;
+            }
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    return None;
+                }
+            }
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use crossterm::cursor::{ Hide, Show, MoveTo };
-use crossterm::terminal::{ Clear, ClearType };
+use crossterm::terminal::{ BeginSynchronizedUpdate, Clear, ClearType, EndSynchronizedUpdate };
 
 use crossterm::terminal::enable_raw_mode;
 use crossterm::terminal::disable_raw_mode;
@@ -7,14 +7,27 @@ use crossterm::terminal::disable_raw_mode;
 use crossterm::terminal::EnterAlternateScreen;
 use crossterm::terminal::LeaveAlternateScreen;
 
+use crossterm::style::{
+    Attribute, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor
+};
+use crossterm::event::{
+    DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture, Event, poll, read
+};
 use crossterm::{ queue, Command };
 
 use std::io::{stdout, Write};
 use std::error::Error;
+use std::time::Duration;
 
 // use super::cursor::Location;
 use crate::core::Location;
+use crate::core::backend::Backend;
+use crate::core::clipboard::osc52;
+use crate::core::frame::Frame;
+use crate::core::theme::Style;
 
+/// The terminal the editor runs in, driven through crossterm.
+#[derive(Default)]
 pub struct Terminal;
 
 /// Represents the size of the terminal window.
@@ -31,7 +44,7 @@ impl Size {
 }
 
 /// Represents a position in the terminal window.
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
 pub struct Position{
     pub row: usize,
     pub col: usize,
@@ -57,13 +70,71 @@ impl From<Location> for Position {
     }
 }
 
+impl Backend for Terminal {
+/// Prepares the terminal, and makes sure a panic restores it before the
+/// panic message is shown.
+    fn initialize(&mut self) -> Result<(), Box<dyn Error>> {
+        let current_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |panic_info| {
+            let _ = Self::restore();
+            current_hook(panic_info);
+        }));
+        Self::setup()
+    }
+
+    fn terminate(&mut self) -> Result<(), Box<dyn Error>> {
+        Self::restore()
+    }
+
+    fn size(&self) -> Result<Size, Box<dyn Error>> {
+        Self::get_size()
+    }
+
+    fn draw(&mut self, frame: &Frame, previous: Option<&Frame>, cursor: Option<Position>) -> Result<(), Box<dyn Error>> {
+        // Hide the cursor to prevent flickering during updates
+        Self::begin_synchronized_update()?;
+        Self::hide_caret()?;
+        Self::draw_changes(frame, previous)?;
+        if let Some(cursor) = cursor {
+            Self::move_cursor_to(cursor)?;
+            Self::show_caret()?;
+        }
+        Self::end_synchronized_update()?;
+        Self::execute()
+    }
+
+    fn print_line(&mut self, text: &str) -> Result<(), Box<dyn Error>> {
+        Self::print(text)?;
+        Self::print("\r\n")?;
+        Self::execute()
+    }
+
+/// Sends `text` as an OSC 52 sequence. Terminals that don't support it
+/// ignore it.
+    fn set_clipboard(&mut self, text: &str) -> Result<(), Box<dyn Error>> {
+        Self::print(&osc52(text))?;
+        Self::execute()
+    }
+
+    fn poll_event(&mut self, timeout: Duration) -> Result<Option<Event>, Box<dyn Error>> {
+        if poll(timeout)? {
+            Ok(Some(read()?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
 impl Terminal {
 /// Initializes the terminal.
 /// 
 /// Enables raw mode, clears the screen, and moves the cursor to the top-left corner.
-    pub fn initialize() -> Result<(), Box<dyn Error>> {
+    fn setup() -> Result<(), Box<dyn Error>> {
         enable_raw_mode()?;
         Self::enter_alternate_screen()?;
+        // Pasted text then arrives as a single event instead of keystrokes.
+        Self::queue_command(EnableBracketedPaste)?;
+        Self::queue_command(EnableMouseCapture)?;
         Self::clear_screen()?;
         Self::move_cursor_to(Position::new(0, 0))?;
         Self::execute()?;
@@ -73,7 +144,9 @@ impl Terminal {
 /// Terminates the terminal.
 /// 
 /// Clears the screen and disables raw mode.
-    pub fn terminate() -> Result<(), Box<dyn Error>> {
+    fn restore() -> Result<(), Box<dyn Error>> {
+        Self::queue_command(DisableMouseCapture)?;
+        Self::queue_command(DisableBracketedPaste)?;
         Self::leave_alternate_screen()?;
         Self::clear_screen()?;
         disable_raw_mode()?;
@@ -102,12 +175,6 @@ impl Terminal {
         Ok(())
     }
 
-/// Clears current line
-    pub fn clear_line() -> Result<(), Box<dyn Error>> {
-        Self::queue_command(Clear(ClearType::CurrentLine))?;
-        Ok(())
-    }
-
 /// Hides the terminal caret.
 /// 
 /// Returns an error if the operation fails.
@@ -137,10 +204,80 @@ impl Terminal {
         Ok(())
     }
 
-    pub fn print_line(row: usize, line_text: &str) -> Result<(), Box<dyn Error>> {
-        Self::move_cursor_to(Position::new(row, 0))?;
-        Self::clear_line()?;
-        Self::print(line_text)?;
+/// Sends the cells of `frame` that differ from `previous`, the frame
+/// currently on screen, to the terminal. Without a previous frame the
+/// screen is cleared and everything is drawn.
+///
+/// The cursor is only moved where the changed cells are not adjacent, and
+/// the style is only set where it changes.
+    fn draw_changes(frame: &Frame, previous: Option<&Frame>) -> Result<(), Box<dyn Error>> {
+        let blank;
+        let previous = if let Some(previous) = previous {
+            previous
+        } else {
+            Self::reset_style()?;
+            Self::clear_screen()?;
+            blank = Frame::new(frame.size());
+            &blank
+        };
+        // Where the terminal's cursor is, and the style it draws in, once known.
+        let mut cursor: Option<Position> = None;
+        let mut style: Option<Style> = None;
+        for (position, cell) in frame.diff(previous) {
+            if cursor != Some(position) {
+                Self::move_cursor_to(position)?;
+            }
+            if style != Some(cell.style) {
+                Self::set_style(cell.style)?;
+                style = Some(cell.style);
+            }
+            Self::print(&cell.symbol)?;
+            cursor = Some(Position::new(position.row, position.col.saturating_add(cell.width())));
+        }
+        if style.is_some() {
+            Self::reset_style()?;
+        }
+        Ok(())
+    }
+
+/// Tells the terminal to hold back drawing until `end_synchronized_update`,
+/// so an update appears at once. Terminals that don't support it ignore it.
+    pub fn begin_synchronized_update() -> Result<(), Box<dyn Error>> {
+        Self::queue_command(BeginSynchronizedUpdate)?;
+        Ok(())
+    }
+
+    pub fn end_synchronized_update() -> Result<(), Box<dyn Error>> {
+        Self::queue_command(EndSynchronizedUpdate)?;
+        Ok(())
+    }
+
+    // Replaces the current colors and attributes with `style`.
+    fn set_style(style: Style) -> Result<(), Box<dyn Error>> {
+        Self::queue_command(SetAttribute(Attribute::Reset))?;
+        if let Some(foreground) = style.foreground {
+            Self::queue_command(SetForegroundColor(foreground))?;
+        }
+        if let Some(background) = style.background {
+            Self::queue_command(SetBackgroundColor(background))?;
+        }
+        let attributes = [
+            (style.bold, Attribute::Bold),
+            (style.italic, Attribute::Italic),
+            (style.underline, Attribute::Underlined),
+            (style.reverse, Attribute::Reverse),
+        ];
+        for (enabled, attribute) in attributes {
+            if enabled {
+                Self::queue_command(SetAttribute(attribute))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn reset_style() -> Result<(), Box<dyn Error>> {
+        Self::queue_command(SetAttribute(Attribute::Reset))?;
+        Self::queue_command(ResetColor)?;
         Ok(())
     }
 
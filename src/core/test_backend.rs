@@ -0,0 +1,135 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::time::Duration;
+
+use crossterm::event::{ Event, KeyCode, KeyEvent, KeyModifiers };
+
+use crate::core::backend::Backend;
+use crate::core::frame::Frame;
+use crate::core::terminal::{ Position, Size };
+
+/// A backend without a terminal, for tests of whole editing sessions.
+///
+/// It replays the events queued up front, then reports its input as
+/// closed. Drawing applies only the changes to its own cell grid, the way
+/// a terminal would, so the grid also shows mistakes in what was sent.
+#[derive(Default)]
+pub struct TestBackend {
+    size: Size,
+    events: VecDeque<Event>,
+    screen: Frame,
+    cursor: Position,
+    cursor_visible: bool,
+    clipboard: Option<String>,
+}
+
+impl TestBackend {
+    pub fn new(size: Size) -> Self {
+        Self {
+            size,
+            events: VecDeque::new(),
+            screen: Frame::new(size),
+            cursor: Position::default(),
+            cursor_visible: false,
+            clipboard: None,
+        }
+    }
+
+    pub fn push_event(&mut self, event: Event) {
+        self.events.push_back(event);
+    }
+
+/// Queues a key press.
+    pub fn push_key(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+        self.push_event(Event::Key(KeyEvent::new(code, modifiers)));
+    }
+
+/// Queues `text` as typed, with Enter for each line break.
+    pub fn push_text(&mut self, text: &str) {
+        for character in text.chars() {
+            match character {
+                '\n' => self.push_key(KeyCode::Enter, KeyModifiers::NONE),
+                character => self.push_key(KeyCode::Char(character), KeyModifiers::NONE),
+            }
+        }
+    }
+
+    pub const fn screen(&self) -> &Frame {
+        &self.screen
+    }
+
+/// Where the cursor was last shown.
+    pub const fn cursor(&self) -> Position {
+        self.cursor
+    }
+
+    pub const fn is_cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+/// What was last put on the system clipboard.
+    pub fn clipboard(&self) -> Option<&str> {
+        self.clipboard.as_deref()
+    }
+
+/// The text of every row on screen, without trailing blanks.
+    pub fn lines(&self) -> Vec<String> {
+        (0..self.screen.size().height)
+            .map(|row| self.screen.row_text(row).trim_end().to_string())
+            .collect()
+    }
+}
+
+impl Backend for TestBackend {
+    fn initialize(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn terminate(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn size(&self) -> Result<Size, Box<dyn Error>> {
+        Ok(self.size)
+    }
+
+    fn draw(&mut self, frame: &Frame, previous: Option<&Frame>, cursor: Option<Position>) -> Result<(), Box<dyn Error>> {
+        let blank;
+        let previous = if let Some(previous) = previous {
+            previous
+        } else {
+            self.screen = Frame::new(frame.size());
+            blank = Frame::new(frame.size());
+            &blank
+        };
+        for (position, cell) in frame.diff(previous) {
+            self.screen.set_cell(position, cell.clone());
+        }
+        if let Some(cursor) = cursor {
+            self.cursor = cursor;
+        }
+        self.cursor_visible = cursor.is_some();
+        Ok(())
+    }
+
+    fn print_line(&mut self, _text: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn set_clipboard(&mut self, text: &str) -> Result<(), Box<dyn Error>> {
+        self.clipboard = Some(text.to_string());
+        Ok(())
+    }
+
+    fn poll_event(&mut self, _timeout: Duration) -> Result<Option<Event>, Box<dyn Error>> {
+        let event = self.events.pop_front();
+        if let Some(Event::Resize(width, height)) = event {
+            self.size = Size { height: usize::from(height), width: usize::from(width) };
+        }
+        Ok(event)
+    }
+
+    fn is_input_closed(&self) -> bool {
+        self.events.is_empty()
+    }
+}
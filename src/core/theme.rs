@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crossterm::style::Color;
+use serde::Deserialize;
+
+// Themes that ship with the editor. The first one is used unless another is picked.
+const BUILTIN_THEMES: &[&str] = &[
+    include_str!("../../assets/themes/default.toml"),
+    include_str!("../../assets/themes/dusk.toml"),
+];
+
+// The 16 standard colors with the RGB values xterm uses for them.
+const ANSI_COLORS: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::DarkRed, (205, 0, 0)),
+    (Color::DarkGreen, (0, 205, 0)),
+    (Color::DarkYellow, (205, 205, 0)),
+    (Color::DarkBlue, (0, 0, 238)),
+    (Color::DarkMagenta, (205, 0, 205)),
+    (Color::DarkCyan, (0, 205, 205)),
+    (Color::Grey, (229, 229, 229)),
+    (Color::DarkGrey, (127, 127, 127)),
+    (Color::Red, (255, 0, 0)),
+    (Color::Green, (0, 255, 0)),
+    (Color::Yellow, (255, 255, 0)),
+    (Color::Blue, (92, 92, 255)),
+    (Color::Magenta, (255, 0, 255)),
+    (Color::Cyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+// The levels of each channel in the 6x6x6 cube of the 256-color palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// How many colors the terminal can show.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorSupport {
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+impl ColorSupport {
+/// Guesses the terminal's capability from `COLORTERM` and `TERM`.
+    pub fn detect() -> Self {
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+        let term = std::env::var("TERM").unwrap_or_default();
+        if matches!(colorterm.as_str(), "truecolor" | "24bit") {
+            Self::TrueColor
+        } else if term.contains("256color") {
+            Self::Ansi256
+        } else {
+            Self::Ansi16
+        }
+    }
+
+    // The closest color this terminal can show.
+    fn adapt(self, color: Color) -> Color {
+        match (self, color) {
+            (Self::TrueColor, _) | (_, Color::Reset) => color,
+            (Self::Ansi256, Color::Rgb { r, g, b }) => Color::AnsiValue(Self::nearest_256((r, g, b))),
+            (Self::Ansi16, Color::Rgb { r, g, b }) => Self::nearest_16((r, g, b)),
+            (Self::Ansi16, Color::AnsiValue(index)) => Self::nearest_16(Self::ansi_value_rgb(index)),
+            _ => color,
+        }
+    }
+
+    fn nearest_16(rgb: (u8, u8, u8)) -> Color {
+        ANSI_COLORS
+            .iter()
+            .min_by_key(|(_, candidate)| Self::distance(rgb, *candidate))
+            .map_or(Color::Reset, |(color, _)| *color)
+    }
+
+    // Picks between the closest color of the 6x6x6 cube and the closest grey.
+    fn nearest_256(rgb: (u8, u8, u8)) -> u8 {
+        let level = |channel: u8| {
+            CUBE_LEVELS
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, value)| channel.abs_diff(**value))
+                .map_or(0, |(index, _)| index)
+        };
+        let (r, g, b) = (level(rgb.0), level(rgb.1), level(rgb.2));
+        let cube = (CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]);
+        let cube_index = 16_usize
+            .saturating_add(r.saturating_mul(36))
+            .saturating_add(g.saturating_mul(6))
+            .saturating_add(b);
+
+        let average = (u16::from(rgb.0) + u16::from(rgb.1) + u16::from(rgb.2)) / 3;
+        let grey_step = u8::try_from(average.saturating_sub(3) / 10).unwrap_or(23).min(23);
+        let grey_value = grey_step.saturating_mul(10).saturating_add(8);
+        let grey = (grey_value, grey_value, grey_value);
+
+        if Self::distance(rgb, grey) < Self::distance(rgb, cube) {
+            grey_step.saturating_add(232)
+        } else {
+            u8::try_from(cube_index).unwrap_or(u8::MAX)
+        }
+    }
+
+    fn ansi_value_rgb(index: u8) -> (u8, u8, u8) {
+        match index {
+            0..=15 => ANSI_COLORS[usize::from(index)].1,
+            16..=231 => {
+                let cube = usize::from(index - 16);
+                (CUBE_LEVELS[cube / 36], CUBE_LEVELS[cube / 6 % 6], CUBE_LEVELS[cube % 6])
+            }
+            _ => {
+                let value = (index - 232).saturating_mul(10).saturating_add(8);
+                (value, value, value)
+            }
+        }
+    }
+
+    fn distance(left: (u8, u8, u8), right: (u8, u8, u8)) -> u32 {
+        let channel = |a: u8, b: u8| u32::from(a.abs_diff(b)).pow(2);
+        channel(left.0, right.0) + channel(left.1, right.1) + channel(left.2, right.2)
+    }
+}
+
+/// Colors and text attributes for one kind of output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Style {
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub reverse: bool,
+}
+
+impl Style {
+/// This style drawn on top of `base`: whatever it leaves unset comes from `base`.
+    pub fn over(self, base: Self) -> Self {
+        Self {
+            foreground: self.foreground.or(base.foreground),
+            background: self.background.or(base.background),
+            bold: self.bold || base.bold,
+            italic: self.italic || base.italic,
+            underline: self.underline || base.underline,
+            reverse: self.reverse || base.reverse,
+        }
+    }
+}
+
+// A theme file as written on disk.
+#[derive(Deserialize)]
+struct ThemeFile {
+    name: Option<String>,
+    #[serde(default)]
+    palette: HashMap<String, String>,
+    #[serde(default)]
+    styles: HashMap<String, StyleSpec>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StyleSpec {
+    // Only a foreground color.
+    Foreground(String),
+    Full {
+        fg: Option<String>,
+        bg: Option<String>,
+        #[serde(default)]
+        modifiers: Vec<String>,
+    },
+}
+
+/// Maps highlight scopes and UI elements to styles.
+///
+/// Scopes are dotted names such as `function.macro` or `ui.statusbar`;
+/// a scope without its own style uses that of its closest parent
+/// (`function`, then nothing).
+///
+/// Themes are TOML files with an optional `name`, a `palette` of named
+/// colors and a `styles` table:
+///
+/// ```toml
+/// name = "example"
+///
+/// [palette]
+/// sand = "#d7c483"
+///
+/// [styles]
+/// comment = "dark_grey"
+/// "ui.statusbar" = { fg = "black", bg = "sand", modifiers = ["bold"] }
+/// ```
+///
+/// Colors are names of the 16 terminal colors, `#rrggbb`, a 256-color
+/// palette index or a palette entry.
+#[derive(Clone, Default)]
+pub struct Theme {
+    name: String,
+    styles: HashMap<String, Style>,
+}
+
+impl Theme {
+/// The built-in themes followed by the ones in the user's theme directory,
+/// adapted to `color_support`. Theme files that fail to load are reported
+/// by name next to the error.
+    pub fn load_all(color_support: ColorSupport) -> (Vec<Self>, Vec<String>) {
+        let mut themes: Vec<Self> = BUILTIN_THEMES
+            .iter()
+            .filter_map(|source| Self::parse(source, "builtin", color_support).ok())
+            .collect();
+        let mut errors = Vec::new();
+
+        let mut paths: Vec<PathBuf> = Self::theme_dir()
+            .and_then(|dir| fs::read_dir(dir).ok())
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "toml"))
+            .collect();
+        paths.sort();
+        for path in paths {
+            match Self::load(&path, color_support) {
+                Ok(theme) => {
+                    // A user theme replaces a built-in one of the same name.
+                    themes.retain(|existing| existing.name != theme.name);
+                    themes.push(theme);
+                }
+                Err(err) => errors.push(format!("{}: {err}", path.display())),
+            }
+        }
+        (themes, errors)
+    }
+
+    fn load(path: &Path, color_support: ColorSupport) -> Result<Self, Box<dyn Error>> {
+        let source = fs::read_to_string(path)?;
+        let stem = path.file_stem().map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+        Self::parse(&source, &stem, color_support)
+    }
+
+    fn parse(source: &str, default_name: &str, color_support: ColorSupport) -> Result<Self, Box<dyn Error>> {
+        let file: ThemeFile = toml::from_str(source)?;
+        let color = |value: &str| -> Result<Color, Box<dyn Error>> {
+            let value = file.palette.get(value).map_or(value, String::as_str);
+            Ok(color_support.adapt(Self::parse_color(value)?))
+        };
+        let mut styles = HashMap::new();
+        for (scope, spec) in &file.styles {
+            let style = match spec {
+                StyleSpec::Foreground(foreground) => Style {
+                    foreground: Some(color(foreground)?),
+                    ..Style::default()
+                },
+                StyleSpec::Full { fg, bg, modifiers } => {
+                    let mut style = Style {
+                        foreground: fg.as_deref().map(color).transpose()?,
+                        background: bg.as_deref().map(color).transpose()?,
+                        ..Style::default()
+                    };
+                    for modifier in modifiers {
+                        match modifier.as_str() {
+                            "bold" => style.bold = true,
+                            "italic" => style.italic = true,
+                            "underline" | "underlined" => style.underline = true,
+                            "reverse" | "reversed" => style.reverse = true,
+                            _ => return Err(format!("unknown modifier {modifier:?}").into()),
+                        }
+                    }
+                    style
+                }
+            };
+            styles.insert(scope.clone(), style);
+        }
+        Ok(Self {
+            name: file.name.unwrap_or_else(|| default_name.to_string()),
+            styles,
+        })
+    }
+
+    fn parse_color(value: &str) -> Result<Color, Box<dyn Error>> {
+        if let Some(hex) = value.strip_prefix('#') && hex.len() == 6 {
+            let channel = |range| u8::from_str_radix(&hex[range], 16);
+            return Ok(Color::Rgb { r: channel(0..2)?, g: channel(2..4)?, b: channel(4..6)? });
+        }
+        if let Ok(index) = value.parse::<u8>() {
+            return Ok(Color::AnsiValue(index));
+        }
+        let color = match value.to_lowercase().replace('-', "_").as_str() {
+            "reset" | "default" => Color::Reset,
+            "black" => Color::Black,
+            "dark_grey" | "dark_gray" => Color::DarkGrey,
+            "red" => Color::Red,
+            "dark_red" => Color::DarkRed,
+            "green" => Color::Green,
+            "dark_green" => Color::DarkGreen,
+            "yellow" => Color::Yellow,
+            "dark_yellow" => Color::DarkYellow,
+            "blue" => Color::Blue,
+            "dark_blue" => Color::DarkBlue,
+            "magenta" => Color::Magenta,
+            "dark_magenta" => Color::DarkMagenta,
+            "cyan" => Color::Cyan,
+            "dark_cyan" => Color::DarkCyan,
+            "white" => Color::White,
+            "grey" | "gray" => Color::Grey,
+            _ => return Err(format!("unknown color {value:?}").into()),
+        };
+        Ok(color)
+    }
+
+    fn theme_dir() -> Option<PathBuf> {
+        let base = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(base.join("zen_quill").join("themes"))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+/// The style of `scope`, falling back to its parent scopes.
+    pub fn style(&self, scope: &str) -> Style {
+        let mut scope = scope;
+        loop {
+            if let Some(style) = self.styles.get(scope) {
+                return *style;
+            }
+            match scope.rfind('.') {
+                Some(dot) => scope = &scope[..dot],
+                None => return Style::default(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THEME: &str = r##"
+        [palette]
+        sand = "#d7c483"
+
+        [styles]
+        function = "blue"
+        "ui.statusbar" = { fg = "black", bg = "sand", modifiers = ["bold"] }
+    "##;
+
+    #[test]
+    fn scopes_fall_back_to_their_parents() {
+        let theme = Theme::parse(THEME, "test", ColorSupport::TrueColor).unwrap();
+        assert_eq!(theme.name(), "test");
+        assert_eq!(theme.style("function.macro.builtin").foreground, Some(Color::Blue));
+        assert_eq!(theme.style("function").foreground, Some(Color::Blue));
+        assert_eq!(theme.style("functions"), Style::default());
+        assert_eq!(theme.style("keyword"), Style::default());
+        let statusbar = theme.style("ui.statusbar.inactive");
+        assert_eq!(statusbar.background, Some(Color::Rgb { r: 0xd7, g: 0xc4, b: 0x83 }));
+        assert!(statusbar.bold);
+    }
+
+    #[test]
+    fn colors_fall_back_to_what_the_terminal_can_show() {
+        let theme = Theme::parse(THEME, "test", ColorSupport::Ansi256).unwrap();
+        assert_eq!(theme.style("ui.statusbar").background, Some(Color::AnsiValue(186)));
+        let theme = Theme::parse(THEME, "test", ColorSupport::Ansi16).unwrap();
+        assert_eq!(theme.style("ui.statusbar").background, Some(Color::Grey));
+        assert_eq!(ColorSupport::Ansi16.adapt(Color::AnsiValue(196)), Color::Red);
+        assert_eq!(ColorSupport::Ansi256.adapt(Color::Rgb { r: 18, g: 18, b: 18 }), Color::AnsiValue(233));
+    }
+
+    #[test]
+    fn a_style_over_another_fills_in_what_it_leaves_unset() {
+        let top = Style { foreground: Some(Color::Red), italic: true, ..Style::default() };
+        let base = Style { foreground: Some(Color::Blue), background: Some(Color::Black), ..Style::default() };
+        let style = top.over(base);
+        assert_eq!((style.foreground, style.background, style.italic), (Some(Color::Red), Some(Color::Black), true));
+    }
+
+    #[test]
+    fn bad_colors_and_modifiers_are_errors() {
+        assert!(Theme::parse("[styles]\nkeyword = \"mauve\"", "test", ColorSupport::TrueColor).is_err());
+        assert!(Theme::parse("[styles]\nkeyword = { modifiers = [\"blink\"] }", "test", ColorSupport::TrueColor).is_err());
+    }
+
+    #[test]
+    fn the_builtin_themes_parse() {
+        for source in BUILTIN_THEMES {
+            assert!(Theme::parse(source, "builtin", ColorSupport::Ansi16).is_ok());
+        }
+    }
+}
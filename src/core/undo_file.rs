@@ -0,0 +1,131 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ropey::Rope;
+
+use crate::core::history::History;
+
+const HEADER: &str = "zen_quill-undo 1";
+
+// FNV-1a is used because, unlike `DefaultHasher`, its output is stable
+// across Rust releases, which matters for files that outlive the binary.
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// Persists undo histories in a directory, by default
+/// `$XDG_STATE_HOME/zen_quill/undo` (or `~/.local/state/zen_quill/undo`).
+///
+/// Every file gets its own history file, named after a hash of its canonical
+/// path. The history stores a hash of the text it belongs to and is only
+/// restored while the file on disk still has that content.
+pub struct UndoFile;
+
+impl UndoFile {
+    pub fn save(dir: &Path, file_name: &str, text: &Rope, history: &History) -> Result<(), Box<dyn Error>> {
+        let Some(path) = Self::path_for(dir, file_name) else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let contents = format!(
+            "{HEADER}\nhash {:016x}\n{}",
+            Self::content_hash(text),
+            history.serialize(),
+        );
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+/// Returns the stored history for `file_name` if it was saved for exactly `text`.
+    pub fn load(dir: &Path, file_name: &str, text: &Rope) -> Option<History> {
+        let contents = fs::read_to_string(Self::path_for(dir, file_name)?).ok()?;
+        let rest = contents.strip_prefix(HEADER)?.strip_prefix('\n')?;
+        let (hash_line, rest) = rest.split_once('\n')?;
+        let hash = u64::from_str_radix(hash_line.strip_prefix("hash ")?, 16).ok()?;
+        if hash != Self::content_hash(text) {
+            return None;
+        }
+        History::deserialize(rest)
+    }
+
+/// The directory histories are kept in unless another one is given.
+    pub fn default_dir() -> Option<PathBuf> {
+        let base = match std::env::var_os("XDG_STATE_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".local").join("state"),
+        };
+        Some(base.join("zen_quill").join("undo"))
+    }
+
+    fn path_for(dir: &Path, file_name: &str) -> Option<PathBuf> {
+        let canonical = fs::canonicalize(Path::new(file_name)).ok()?;
+        let key = Self::fnv1a(canonical.to_string_lossy().as_bytes());
+        Some(dir.join(format!("{key:016x}.undo")))
+    }
+
+    fn content_hash(text: &Rope) -> u64 {
+        text.chunks()
+            .fold(FNV_OFFSET, |hash, chunk| Self::fnv1a_continue(hash, chunk.as_bytes()))
+    }
+
+    fn fnv1a(bytes: &[u8]) -> u64 {
+        Self::fnv1a_continue(FNV_OFFSET, bytes)
+    }
+
+    fn fnv1a_continue(hash: u64, bytes: &[u8]) -> u64 {
+        bytes.iter().fold(hash, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::history::Edit;
+
+    // A history with a redo step and edits spanning lines.
+    fn history() -> History {
+        let mut history = History::default();
+        history.record(Edit::Insert { at: 0, text: "two\nlines".to_string() }, false);
+        history.record(Edit::Delete { at: 3, text: "\n".to_string() }, false);
+        history.record(Edit::Insert { at: 3, text: "日本".to_string() }, false);
+        history.undo();
+        history
+    }
+
+    #[test]
+    fn histories_survive_serializing() {
+        let serialized = history().serialize();
+        let restored = History::deserialize(&serialized).unwrap();
+        assert_eq!(restored.serialize(), serialized);
+        let (without_redo, _) = serialized.split_once("redo").unwrap();
+        assert!(History::deserialize(without_redo).is_none());
+        assert!(History::deserialize("undo 1\nfoo\n").is_none());
+    }
+
+    #[test]
+    fn fnv1a_matches_the_reference_values() {
+        assert_eq!(UndoFile::fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(UndoFile::fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(UndoFile::fnv1a(b"foobar"), 0x8594_4171_f739_67e8);
+        assert_eq!(UndoFile::content_hash(&Rope::from_str("foobar")), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn a_history_is_only_restored_for_the_same_text() {
+        let dir = std::env::temp_dir().join(format!("zen_quill_undo_file_{}", std::process::id()));
+        let file = dir.join("file.txt");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&file, "twolines").unwrap();
+        let file_name = file.to_string_lossy();
+        let text = Rope::from_str("twolines");
+        UndoFile::save(&dir, &file_name, &text, &history()).unwrap();
+        let restored = UndoFile::load(&dir, &file_name, &text).unwrap();
+        assert_eq!(restored.serialize(), history().serialize());
+        assert!(UndoFile::load(&dir, &file_name, &Rope::from_str("changed")).is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,9 +1,18 @@
-use crate::core::line::Line;
 use crate::core::Position;
 use crate::core::command::{Direction, EditorCommand};
 
-use crate::core::terminal::{ Size, Terminal };
+use crate::core::frame::Frame;
+use crate::core::terminal::Size;
 use crate::core::buffer::Buffer;
+use crate::core::line::Line;
+use crate::core::status_bar::DocumentStatus;
+use crate::core::annotated_string::{ Annotation, AnnotationType };
+use crate::core::pattern::Pattern;
+use crate::core::theme::Theme;
+
+use std::error::Error;
+use std::ops::Range;
+use std::path::{ Path, PathBuf };
 
 #[derive(Clone, Copy, Default)]
 pub struct Location {
@@ -17,12 +26,69 @@ impl From<Position> for Location {
     }
 }
 
+/// State of an ongoing incremental search.
+struct SearchInfo {
+    // Where the cursor and scroll position were before the search began,
+    // so that dismissing the search can restore them.
+    prev_location: Location,
+    prev_scroll_offset: Position,
+    pattern: Option<Pattern>,
+}
+
+/// State of an ongoing find-and-replace. Positions are char indices into the buffer.
+struct ReplaceInfo {
+    pattern: Pattern,
+    replacement: String,
+    // Where the next match is looked for.
+    from: usize,
+    // Where the replace started. Matches are taken from there to the end of
+    // the buffer, then from the start of the buffer up to this point.
+    stop_at: usize,
+    wrapped: bool,
+    // The match waiting for confirmation and the text that would replace it.
+    current: Option<(Range<usize>, String)>,
+    replaced: usize,
+}
+
+/// What the gutter left of the text shows.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+enum LineNumbers {
+    #[default]
+    Hidden,
+    Absolute,
+    // Distances from the cursor line, which shows its own absolute number.
+    Relative,
+}
+
+impl LineNumbers {
+    const fn cycled(self) -> Self {
+        match self {
+            Self::Hidden => Self::Absolute,
+            Self::Absolute => Self::Relative,
+            Self::Relative => Self::Hidden,
+        }
+    }
+}
+
 pub struct View{
     buffer: Buffer,
     need_redraw: bool,
     size: Size,
     text_location: Location,
+    // Where the selection started; it reaches from here to the cursor.
+    selection_anchor: Option<Location>,
+    // The top line and the leftmost column on screen. With soft wrap there
+    // is nothing to scroll horizontally, and `col` instead counts the rows
+    // of the top line that are scrolled past.
     scroll_offset: Position,
+    search_info: Option<SearchInfo>,
+    replace_info: Option<ReplaceInfo>,
+    line_numbers: LineNumbers,
+    soft_wrap: bool,
+    // Wrap at this column instead of the window's edge, if it is narrower.
+    wrap_column: Option<usize>,
+    // The line the cursor was on when the text was last drawn.
+    drawn_cursor_line: Option<usize>,
 }
 
 impl Default for View {
@@ -32,81 +98,866 @@ impl Default for View {
             need_redraw: true, 
             size: Size::default(),
             text_location: Location::default(),
+            selection_anchor: None,
             scroll_offset: Position::default(),
+            search_info: None,
+            replace_info: None,
+            line_numbers: LineNumbers::default(),
+            soft_wrap: false,
+            wrap_column: None,
+            drawn_cursor_line: None,
         }
     }
 }
 
 impl View {
 
-    pub fn render_line(row: usize, line_text: &str) {
-        if let Err(err) = Terminal::print_line(row, line_text) {
-            eprintln!("Fail to Render: {err}");
-        }
-    }
-
 /// Draws the rows of the editor on the terminal screen.
 /// 
 /// `truncated_line` is **NOT VERY SAFE**
-    pub fn render(&mut self) {
-        if !self.need_redraw {
+    pub fn render(&mut self, frame: &mut Frame, theme: &Theme) {
+        // The cursor line is styled differently, so moving off it needs a redraw.
+        if !self.need_redraw && self.drawn_cursor_line == Some(self.text_location.line_index) {
             return ;
         }
-        let Size{height, width} = self.size;
+        self.drawn_cursor_line = Some(self.text_location.line_index);
+        let Size{height, ..} = self.size;
+        let gutter_width = self.gutter_width();
         let top = self.scroll_offset.row;
+        self.buffer.highlight(top..top.saturating_add(height));
+        let text_style = theme.style("ui.text");
+        let cursor_line_style = theme.style("ui.cursorline").over(text_style);
+
+        let mut current_row = 0;
+        let mut line_index = top;
+        // When wrapping, the rows of the top line that are scrolled past.
+        let mut skipped_rows = if self.wrap_width().is_some() { self.scroll_offset.col } else { 0 };
+        while current_row < height {
+            let Some(line) = self.buffer.line(line_index) else {
+                frame.set_line(current_row, "~", text_style);
+                current_row = current_row.saturating_add(1);
+                continue;
+            };
+            let mut annotations = self.buffer.syntax_annotations(line_index).to_vec();
+            annotations.extend(self.match_annotations(&line, line_index));
+            if let Some(range) = self.selection() {
+                annotations.extend(self.range_annotation(&range, line_index, AnnotationType::Selection));
+            }
+            let is_cursor_line = line_index == self.text_location.line_index;
+            let line_style = if is_cursor_line { cursor_line_style } else { text_style };
+
+            let rows = self.visible_columns(&line);
+            for (row_in_line, columns) in rows.into_iter().enumerate().skip(skipped_rows) {
+                if current_row >= height {
+                    break;
+                }
+                //truncate line
+                let mut truncated_line = line.get_annotated_visible_substr(columns, &annotations);
+                if gutter_width > 0 {
+                    let number_type = if is_cursor_line {
+                        AnnotationType::CursorLineNumber
+                    } else {
+                        AnnotationType::LineNumber
+                    };
+                    // Only the first row of a wrapped line is numbered.
+                    let number = if row_in_line == 0 {
+                        self.line_number(line_index)
+                    } else {
+                        " ".repeat(gutter_width)
+                    };
+                    truncated_line.prepend(&number, number_type);
+                }
+                frame.set_annotated_line(current_row, &truncated_line, theme, line_style);
+                current_row = current_row.saturating_add(1);
+            }
+            skipped_rows = 0;
+            line_index = line_index.saturating_add(1);
+        }
+
+        self.need_redraw = false;
+
+    }
+
+    // The columns left of the text taken by line numbers: the digits of the
+    // highest number and a space. None if hidden or the window is too narrow.
+    fn gutter_width(&self) -> usize {
+        if self.line_numbers == LineNumbers::Hidden {
+            return 0;
+        }
+        let width = self.buffer.height().max(1).to_string().len().saturating_add(1);
+        if width >= self.size.width {
+            return 0;
+        }
+        width
+    }
+
+    // The columns left for the text itself.
+    fn text_width(&self) -> usize {
+        self.size.width.saturating_sub(self.gutter_width())
+    }
+
+    // The gutter text of a line, right-aligned before the separating space.
+    fn line_number(&self, line_index: usize) -> String {
+        let cursor_line = self.text_location.line_index;
+        let number = match self.line_numbers {
+            LineNumbers::Relative if line_index != cursor_line => line_index.abs_diff(cursor_line),
+            _ => line_index.saturating_add(1),
+        };
+        let digits = self.gutter_width().saturating_sub(1);
+        format!("{number:>digits$} ")
+    }
+
+    // The column lines wrap at, or None if soft wrap is off.
+    fn wrap_width(&self) -> Option<usize> {
+        if !self.soft_wrap {
+            return None;
+        }
+        let width = self.text_width();
+        Some(self.wrap_column.map_or(width, |column| column.min(width)).max(1))
+    }
+
+    // The columns of `line` drawn on each of its rows: those of every
+    // wrapped row, or the horizontally scrolled window.
+    fn visible_columns(&self, line: &Line) -> Vec<Range<usize>> {
+        let Some(wrap_width) = self.wrap_width() else {
+            let left = self.scroll_offset.col;
+            return std::iter::once(left..left.saturating_add(self.text_width())).collect();
+        };
+        let starts = line.wrap_starts(wrap_width);
+        starts
+            .iter()
+            .enumerate()
+            .map(|(row, start)| {
+                let left = line.width_until(*start);
+                let right = starts.get(row.saturating_add(1)).map_or(usize::MAX, |next| line.width_until(*next));
+                // Whitespace hanging past the wrap column is cut off.
+                left..right.min(left.saturating_add(wrap_width))
+            })
+            .collect()
+    }
 
-        for current_row in 0..height {
-            //truncate line
-            if let Some(line) = self.buffer.lines.get(current_row.saturating_add(top)) {
+    // Where each visual row of a line starts, as grapheme indices. Without
+    // soft wrap, or past the end of the buffer, a line is a single row.
+    fn row_starts(&self, line_index: usize) -> Vec<usize> {
+        match (self.wrap_width(), self.buffer.line(line_index)) {
+            (Some(wrap_width), Some(line)) => line.wrap_starts(wrap_width),
+            _ => vec![0],
+        }
+    }
 
-                let left = self.scroll_offset.col;
-                let right = self.scroll_offset.col.saturating_add(width);
+    // The row of its line that a grapheme is drawn on.
+    fn row_in_line(row_starts: &[usize], grapheme_index: usize) -> usize {
+        row_starts
+            .partition_point(|start| *start <= grapheme_index)
+            .saturating_sub(1)
+    }
 
-                let truncated_line = line.get_visible_graphemes(left..right);
-                Self::render_line(current_row, truncated_line.as_str());
+    // The visual row of the cursor, as its line and the row within that line.
+    fn cursor_row(&self) -> (usize, usize) {
+        let Location { line_index, grapheme_index } = self.text_location;
+        (line_index, Self::row_in_line(&self.row_starts(line_index), grapheme_index))
+    }
 
-            }else {
-                Self::render_line(current_row, "~");
+    // How many visual rows `to` is below `from`, counting no further than `limit`.
+    fn rows_between(&self, from: (usize, usize), to: (usize, usize), limit: usize) -> usize {
+        if to <= from {
+            return 0;
+        }
+        if from.0 == to.0 {
+            return to.1.saturating_sub(from.1);
+        }
+        let mut rows = self.row_starts(from.0).len().saturating_sub(from.1);
+        let mut line_index = from.0.saturating_add(1);
+        while line_index < to.0 {
+            if rows >= limit {
+                return limit;
             }
+            rows = rows.saturating_add(self.row_starts(line_index).len());
+            line_index = line_index.saturating_add(1);
         }
+        rows.saturating_add(to.1)
+    }
 
-        self.need_redraw = false;
+    // The visual row `count` rows above `from`, stopping at the first one.
+    fn rows_before(&self, from: (usize, usize), count: usize) -> (usize, usize) {
+        let (mut line_index, mut row) = from;
+        let mut remaining = count;
+        while remaining > row && line_index > 0 {
+            remaining = remaining.saturating_sub(row.saturating_add(1));
+            line_index = line_index.saturating_sub(1);
+            row = self.row_starts(line_index).len().saturating_sub(1);
+        }
+        (line_index, row.saturating_sub(remaining))
+    }
+
+    // The visual row `count` rows below `from`, stopping at the last line.
+    fn rows_after(&self, from: (usize, usize), count: usize) -> (usize, usize) {
+        let (mut line_index, mut row) = from;
+        let last_line = self.buffer.height().saturating_sub(1);
+        for _ in 0..count {
+            if row.saturating_add(1) < self.row_starts(line_index).len() {
+                row = row.saturating_add(1);
+            } else if line_index < last_line {
+                line_index = line_index.saturating_add(1);
+                row = 0;
+            } else {
+                break;
+            }
+        }
+        (line_index, row)
+    }
 
+    // Highlights every match of the current search in a line; the one under
+    // the cursor, or the one a replace is asking about, is marked as selected.
+    fn match_annotations(&self, line: &Line, line_index: usize) -> Vec<Annotation> {
+        if let Some((range, _)) = self.replace_info.as_ref().and_then(|info| info.current.as_ref()) {
+            return self
+                .range_annotation(range, line_index, AnnotationType::SelectedMatch)
+                .into_iter()
+                .collect();
+        }
+        let Some(pattern) = self.search_info.as_ref().and_then(|info| info.pattern.as_ref()) else {
+            return Vec::new();
+        };
+        line.find_all(pattern)
+            .into_iter()
+            .map(|found| {
+                let is_selected = line_index == self.text_location.line_index &&
+                                  found.start == self.text_location.grapheme_index;
+                Annotation {
+                    annotation_type: if is_selected {
+                        AnnotationType::SelectedMatch
+                    } else {
+                        AnnotationType::Match
+                    },
+                    start: found.start,
+                    end: found.end,
+                }
+            })
+            .collect()
+    }
+
+    // The part of a (possibly multi-line) char range that lies on `line_index`.
+    fn range_annotation(
+        &self,
+        range: &Range<usize>,
+        line_index: usize,
+        annotation_type: AnnotationType,
+    ) -> Option<Annotation> {
+        let start = self.buffer.location_of(range.start);
+        let end = self.buffer.location_of(range.end);
+        if line_index < start.line_index || line_index > end.line_index {
+            return None;
+        }
+        Some(Annotation {
+            annotation_type,
+            start: if line_index == start.line_index { start.grapheme_index } else { 0 },
+            end: if line_index == end.line_index { end.grapheme_index } else { usize::MAX },
+        })
     }
 
-    pub fn handle_command(&mut self, command: EditorCommand) {
+/// Applies a command to the view.
+///
+/// Returns an error for commands that touch the file system and fail,
+/// so the editor can report it.
+    pub fn handle_command(&mut self, command: EditorCommand) -> Result<(), Box<dyn Error>> {
         match command {
             EditorCommand::Resize(size) => 
                 self.resize(size),
-            EditorCommand::Move(direction) => 
-                self.move_text_location(&direction),
-            EditorCommand::Quit => (),
+            EditorCommand::Move(direction) => {
+                self.buffer.seal_history();
+                if !self.collapse_selection(&direction) {
+                    self.move_text_location(&direction);
+                }
+            }
+            EditorCommand::Select(direction) =>
+                self.extend_selection(&direction),
+            EditorCommand::Quit |
+            EditorCommand::Dismiss |
+            EditorCommand::GoToLine |
+            EditorCommand::Search |
+            EditorCommand::Replace |
+            EditorCommand::ToggleRegex |
+            EditorCommand::ProjectSearch |
+            EditorCommand::CycleTheme |
+            EditorCommand::Cut |
+            EditorCommand::Copy |
+            EditorCommand::Paste |
+            EditorCommand::SelectRegister => (),
+            EditorCommand::CycleLineNumbers =>
+                self.cycle_line_numbers(),
+            EditorCommand::ToggleSoftWrap =>
+                self.toggle_soft_wrap(),
             EditorCommand::Insert(character) =>
                 self.insert_character(character),
             EditorCommand::Backspace =>
                 self.backspace(),
             EditorCommand::Delete =>
                 self.delete(),
+            EditorCommand::DeleteWordBackward =>
+                self.delete_word_backward(),
+            EditorCommand::DeleteWordForward =>
+                self.delete_word_forward(),
             EditorCommand::Tab =>
                 self.insert_tab(),
             EditorCommand::Enter =>
                 self.insert_newline(),
             EditorCommand::Save =>
-                self.save(),
+                self.save()?,
+            EditorCommand::Undo =>
+                self.undo(),
+            EditorCommand::Redo =>
+                self.redo(),
+            EditorCommand::ToggleLineEnding =>
+                self.buffer.toggle_line_ending(),
+            EditorCommand::CycleEncoding =>
+                self.buffer.set_encoding(self.buffer.encoding().cycled()),
+            EditorCommand::ReopenWithEncoding =>
+                self.reopen_with_encoding()?,
+            #[cfg(feature = "tree-sitter")]
+            EditorCommand::ParentNode =>
+                self.jump_to_parent_node()?,
+            #[cfg(feature = "tree-sitter")]
+            EditorCommand::SelectNode =>
+                self.select_node()?,
+            #[cfg(feature = "tree-sitter")]
+            EditorCommand::NextFunction =>
+                self.jump_to_next_function()?,
+        }
+        Ok(())
+    }
+
+    pub fn load(&mut self, file_name: &str) -> Result<(), Box<dyn Error>> {
+        self.buffer = Buffer::load(file_name, self.buffer.undo_dir().map(Path::to_path_buf))?;
+        self.selection_anchor = None;
+        self.need_redraw = true;
+        Ok(())
+    }
+
+/// Opens another file with the cursor at `location`.
+    pub fn open_at(&mut self, file_name: &str, location: Location) -> Result<(), Box<dyn Error>> {
+        self.buffer = Buffer::load(file_name, self.buffer.undo_dir().map(Path::to_path_buf))?;
+        self.selection_anchor = None;
+        self.search_info = None;
+        self.replace_info = None;
+        self.text_location = location;
+        self.scroll_offset = Position::default();
+        self.snap_to_valid_line();
+        self.snap_to_valid_grapheme();
+        self.scroll_location_into_view();
+        self.need_redraw = true;
+        Ok(())
+    }
+
+/// Switches between no line numbers, absolute and relative ones.
+    pub fn cycle_line_numbers(&mut self) {
+        self.line_numbers = self.line_numbers.cycled();
+        // The gutter takes columns from the text, which may push the cursor out of view.
+        self.scroll_location_into_view();
+        self.need_redraw = true;
+    }
+
+/// Turns soft wrap on or off.
+    pub fn toggle_soft_wrap(&mut self) {
+        self.soft_wrap = !self.soft_wrap;
+        self.scroll_offset.col = 0;
+        self.scroll_location_into_view();
+        self.need_redraw = true;
+    }
+
+/// Wraps lines at `column`, or at the window's edge if it is narrower, and turns soft wrap on.
+    pub fn set_wrap_column(&mut self, column: usize) {
+        self.wrap_column = Some(column);
+        if !self.soft_wrap {
+            self.toggle_soft_wrap();
+        }
+    }
+
+/// Keeps undo histories in `dir` from now on, or nowhere if it is None.
+    pub fn set_undo_dir(&mut self, dir: Option<PathBuf>) {
+        self.buffer.set_undo_dir(dir);
+    }
+
+/// Forces a redraw, e.g. after something else was drawn over the text.
+    pub fn mark_redraw(&mut self) {
+        self.need_redraw = true;
+    }
+
+/// Starts an incremental search from the current cursor position.
+    pub fn enter_search(&mut self) {
+        self.buffer.seal_history();
+        self.clear_selection();
+        self.search_info = Some(SearchInfo {
+            prev_location: self.text_location,
+            prev_scroll_offset: self.scroll_offset,
+            pattern: None,
+        });
+    }
+
+/// Ends the search and leaves the cursor on the current match.
+    pub fn exit_search(&mut self) {
+        self.search_info = None;
+        self.need_redraw = true;
+    }
+
+/// Ends the search and puts the cursor and scroll position back where they were.
+    pub fn dismiss_search(&mut self) {
+        if let Some(search_info) = self.search_info.take() {
+            self.text_location = search_info.prev_location;
+            self.scroll_offset = search_info.prev_scroll_offset;
+            self.scroll_location_into_view();
+        }
+        self.need_redraw = true;
+    }
+
+/// Moves to the first match of `query` at or after where the search began.
+///
+/// With `is_regex` the query is a regular expression; while it does not
+/// compile nothing matches.
+    pub fn search(&mut self, query: &str, is_regex: bool) {
+        let Some(search_info) = &mut self.search_info else {
+            return ;
+        };
+        search_info.pattern = if query.is_empty() {
+            None
+        } else {
+            Pattern::new(query, is_regex).ok()
+        };
+        let from = search_info.prev_location;
+        self.need_redraw = true;
+        let found = search_info
+            .pattern
+            .as_ref()
+            .and_then(|pattern| self.buffer.search_forward(pattern, from));
+        self.move_to_match(found);
+    }
+
+/// Moves to the next match after the cursor, wrapping at the end of the buffer.
+    pub fn search_next(&mut self) {
+        let Some(pattern) = self.search_pattern() else {
+            return ;
+        };
+        let from = Location {
+            line_index: self.text_location.line_index,
+            grapheme_index: self.text_location.grapheme_index.saturating_add(1),
+        };
+        self.move_to_match(self.buffer.search_forward(&pattern, from));
+    }
+
+/// Moves to the previous match before the cursor, wrapping at the start of the buffer.
+    pub fn search_prev(&mut self) {
+        let Some(pattern) = self.search_pattern() else {
+            return ;
+        };
+        self.move_to_match(self.buffer.search_backward(&pattern, self.text_location));
+    }
+
+    fn search_pattern(&self) -> Option<Pattern> {
+        self.search_info.as_ref().and_then(|info| info.pattern.clone())
+    }
+
+    fn move_to_match(&mut self, found: Option<Location>) {
+        if let Some(location) = found {
+            self.text_location = location;
+            self.scroll_location_into_view();
+            self.need_redraw = true;
+        }
+    }
+
+/// Starts replacing matches of `pattern`, beginning at the cursor.
+///
+/// Returns whether there is a first match to confirm.
+    pub fn start_replace(&mut self, pattern: Pattern, replacement: &str) -> bool {
+        self.buffer.seal_history();
+        self.clear_selection();
+        let start = self.char_index_or_end(self.text_location);
+        self.replace_info = Some(ReplaceInfo {
+            pattern,
+            replacement: replacement.to_string(),
+            from: start,
+            stop_at: start,
+            wrapped: false,
+            current: None,
+            replaced: 0,
+        });
+        self.find_next_replacement()
+    }
+
+    // Looks for the next match to confirm and moves the cursor onto it.
+    fn find_next_replacement(&mut self) -> bool {
+        let Some(info) = &mut self.replace_info else {
+            return false;
+        };
+        loop {
+            let within = if info.wrapped { info.from..info.stop_at } else { info.from..usize::MAX };
+            info.current = self.buffer.find_replacement(&info.pattern, &info.replacement, within);
+            if info.current.is_some() || info.wrapped {
+                break;
+            }
+            info.wrapped = true;
+            info.from = 0;
+        }
+        self.need_redraw = true;
+        let Some((range, _)) = &info.current else {
+            return false;
+        };
+        self.text_location = self.buffer.location_of(range.start);
+        self.scroll_location_into_view();
+        true
+    }
+
+/// Replaces the match being confirmed and moves on to the next one.
+///
+/// Returns whether there is another match.
+    pub fn replace_current(&mut self) -> bool {
+        let Some(info) = &mut self.replace_info else {
+            return false;
+        };
+        let Some((range, text)) = info.current.take() else {
+            return false;
+        };
+        self.buffer.replace_range(range.clone(), &text);
+        let inserted = text.chars().count();
+        info.replaced = info.replaced.saturating_add(1);
+        info.from = range.start.saturating_add(inserted);
+        if range.start < info.stop_at {
+            // Text before the starting point changed length.
+            info.stop_at = if range.end <= info.stop_at {
+                info.stop_at.saturating_sub(range.len()).saturating_add(inserted)
+            } else {
+                info.from
+            };
+        }
+        self.find_next_replacement()
+    }
+
+/// Leaves the match being confirmed as it is and moves on to the next one.
+///
+/// Returns whether there is another match.
+    pub fn skip_current(&mut self) -> bool {
+        let Some(info) = &mut self.replace_info else {
+            return false;
+        };
+        let Some((range, _)) = info.current.take() else {
+            return false;
+        };
+        info.from = range.end;
+        self.find_next_replacement()
+    }
+
+/// Replaces the match being confirmed and every match after it as one undo step.
+    pub fn replace_all_remaining(&mut self) {
+        let Some(info) = &mut self.replace_info else {
+            return ;
+        };
+        let mut remaining = Vec::with_capacity(2);
+        if info.wrapped {
+            remaining.push(info.from..info.stop_at);
+        } else {
+            remaining.push(info.from..usize::MAX);
+            remaining.push(0..info.stop_at);
+        }
+        let replaced = self.buffer.replace_all(&info.pattern, &info.replacement, &remaining);
+        info.replaced = info.replaced.saturating_add(replaced);
+        info.current = None;
+    }
+
+/// Ends the replace and returns how many matches were replaced.
+    pub fn end_replace(&mut self) -> usize {
+        let replaced = self.replace_info.take().map_or(0, |info| info.replaced);
+        self.snap_to_valid_line();
+        self.snap_to_valid_grapheme();
+        self.scroll_location_into_view();
+        self.need_redraw = true;
+        replaced
+    }
+
+    #[cfg(feature = "tree-sitter")]
+    fn require_syntax_tree(&self) -> Result<(), Box<dyn Error>> {
+        if self.buffer.has_syntax_tree() {
+            Ok(())
+        } else {
+            Err(format!("No syntax tree for {} files.", self.buffer.file_type()).into())
+        }
+    }
+
+/// Moves the cursor to the start of the syntax node around it.
+    #[cfg(feature = "tree-sitter")]
+    fn jump_to_parent_node(&mut self) -> Result<(), Box<dyn Error>> {
+        self.require_syntax_tree()?;
+        if let Some(location) = self.buffer.parent_node(self.text_location) {
+            self.jump_to(location);
+        }
+        Ok(())
+    }
+
+/// Selects the syntax node under the cursor; repeating it selects the
+/// node around the current selection.
+    #[cfg(feature = "tree-sitter")]
+    fn select_node(&mut self) -> Result<(), Box<dyn Error>> {
+        self.require_syntax_tree()?;
+        let Some(cursor) = self.buffer.char_index(self.text_location) else {
+            return Ok(());
+        };
+        let selected = self.selection().unwrap_or(cursor..cursor);
+        if let Some(node) = self.buffer.enclosing_node(selected) {
+            self.select(self.buffer.location_of(node.end), self.buffer.location_of(node.start));
+        }
+        Ok(())
+    }
+
+/// Moves the cursor to the next function definition.
+    #[cfg(feature = "tree-sitter")]
+    fn jump_to_next_function(&mut self) -> Result<(), Box<dyn Error>> {
+        self.require_syntax_tree()?;
+        if let Some(location) = self.buffer.next_function(self.text_location) {
+            self.jump_to(location);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "tree-sitter")]
+    fn jump_to(&mut self, location: Location) {
+        self.buffer.seal_history();
+        self.clear_selection();
+        self.text_location = location;
+        self.scroll_location_into_view();
+        self.need_redraw = true;
+    }
+
+/// Moves the cursor to the start of a 1-based line number, clamped to the document.
+    pub fn go_to_line(&mut self, line_number: usize) {
+        self.buffer.seal_history();
+        self.clear_selection();
+        self.text_location = Location {
+            line_index: line_number.saturating_sub(1),
+            grapheme_index: 0,
+        };
+        self.snap_to_valid_line();
+        self.scroll_location_into_view();
+    }
+
+/// Reads the file again, decoding it with the encoding after the current one.
+    pub fn reopen_with_encoding(&mut self) -> Result<(), Box<dyn Error>> {
+        let encoding = self.buffer.encoding().next_reading();
+        self.buffer.reload_with_encoding(encoding)?;
+        self.selection_anchor = None;
+        self.snap_to_valid_line();
+        self.snap_to_valid_grapheme();
+        self.scroll_location_into_view();
+        self.need_redraw = true;
+        Ok(())
+    }
+
+/// Describes the document and cursor for the status bar.
+    pub fn get_status(&self) -> DocumentStatus {
+        DocumentStatus {
+            file_name: self.buffer.file_name().map(str::to_string),
+            is_modified: self.buffer.is_modified(),
+            line_count: self.buffer.height(),
+            current_line: self.text_location.line_index.saturating_add(1),
+            current_column: self.text_location.grapheme_index.saturating_add(1),
+            file_type: self.buffer.file_type(),
+            encoding: self.buffer.encoding().name(),
+            line_ending: if self.buffer.has_mixed_line_endings() {
+                "Mixed"
+            } else {
+                self.buffer.line_ending().name()
+            },
         }
     }
 
-    pub fn load(&mut self, file_name: &str) {
-        if let Ok(buffer) = Buffer::load(file_name) {
-            self.buffer = buffer;
+    // The selected char range, or None if nothing is selected.
+    fn selection(&self) -> Option<Range<usize>> {
+        let anchor = self.char_index_or_end(self.selection_anchor?);
+        let cursor = self.char_index_or_end(self.text_location);
+        (anchor != cursor).then(|| anchor.min(cursor)..anchor.max(cursor))
+    }
+
+    // The char index of a location, where the line past the last one is the
+    // end of the buffer.
+    fn char_index_or_end(&self, location: Location) -> usize {
+        self.buffer.char_index(location).unwrap_or_else(|| self.buffer.len_chars())
+    }
+
+    // Moves the cursor, selecting the text it passes over.
+    fn extend_selection(&mut self, direction: &Direction) {
+        self.buffer.seal_history();
+        self.selection_anchor.get_or_insert(self.text_location);
+        self.move_text_location(direction);
+        self.need_redraw = true;
+    }
+
+    fn clear_selection(&mut self) {
+        if self.selection_anchor.take().is_some() {
             self.need_redraw = true;
         }
     }
 
+    // Drops the selection before the cursor moves. Left and Right only put
+    // the cursor at the start or end of a selection; returns whether they did.
+    fn collapse_selection(&mut self, direction: &Direction) -> bool {
+        let selection = self.selection();
+        self.clear_selection();
+        let Some(range) = selection else {
+            return false;
+        };
+        let char_index = match direction {
+            Direction::Left => range.start,
+            Direction::Right => range.end,
+            _ => return false,
+        };
+        self.text_location = self.buffer.location_of(char_index);
+        self.scroll_location_into_view();
+        true
+    }
+
+    // Removes the selected text and puts the cursor where it was. Returns
+    // whether there was anything to remove.
+    fn delete_selection(&mut self) -> bool {
+        let selection = self.selection();
+        let anchor = self.selection_anchor.take();
+        let (Some(range), Some(anchor)) = (selection, anchor) else {
+            return false;
+        };
+        self.buffer.delete_range(anchor, self.text_location);
+        self.text_location = self.buffer.location_of(range.start);
+        self.scroll_location_into_view();
+        self.need_redraw = true;
+        true
+    }
+
+    // Runs an edit at the cursor in place of the selection, if any, so that
+    // removing the selection and the edit are undone as one step.
+    fn replace_selection(&mut self, edit: impl FnOnce(&mut Self)) {
+        if self.selection().is_none() {
+            edit(self);
+            return ;
+        }
+        self.buffer.begin_group();
+        self.delete_selection();
+        edit(self);
+        self.buffer.end_group();
+    }
+
+/// The selected text, or None if nothing is selected.
+    pub fn selected_text(&self) -> Option<String> {
+        self.selection().map(|range| self.buffer.text_in(range))
+    }
+
+/// Removes the selected text and returns it, or None if nothing is selected.
+    pub fn cut_selection(&mut self) -> Option<String> {
+        let text = self.selected_text()?;
+        self.delete_selection();
+        Some(text)
+    }
+
+/// Inserts `text` at the cursor in place of the selection, if any, and
+/// moves the cursor after it.
+    pub fn paste(&mut self, text: &str) {
+        let from = self.selection_anchor.take().unwrap_or(self.text_location);
+        self.text_location = self.buffer.replace_text(from, self.text_location, text);
+        self.scroll_location_into_view();
+        self.need_redraw = true;
+    }
+
+/// The location of the grapheme drawn at `position` in the text area.
+///
+/// A position past the end of a line is at its end, and one below the
+/// text at the end of the last line.
+    pub fn location_at(&self, position: Position) -> Location {
+        let column = position.col.saturating_sub(self.gutter_width());
+        let end_of_text = || {
+            let line_index = self.buffer.height().saturating_sub(1);
+            Location { grapheme_index: self.buffer.grapheme_count(line_index), line_index }
+        };
+        let (line_index, row, column) = if self.wrap_width().is_some() {
+            let top = (self.scroll_offset.row, self.scroll_offset.col);
+            let (line_index, row) = self.rows_after(top, position.row);
+            if self.rows_between(top, (line_index, row), position.row) < position.row {
+                return end_of_text();
+            }
+            (line_index, row, column)
+        } else {
+            (self.scroll_offset.row.saturating_add(position.row), 0, column.saturating_add(self.scroll_offset.col))
+        };
+        let Some(line) = self.buffer.line(line_index) else {
+            return end_of_text();
+        };
+        let starts = self.row_starts(line_index);
+        let start = starts.get(row).copied().unwrap_or_default();
+        // As when moving by row, the end of a row that continues is the
+        // start of the next one.
+        let end = starts
+            .get(row.saturating_add(1))
+            .map_or(line.grapheme_count(), |next| next.saturating_sub(1));
+        Location { grapheme_index: line.grapheme_at_column(start..end, column), line_index }
+    }
+
+/// Puts the cursor on the grapheme at `position` in the text area.
+    pub fn click(&mut self, position: Position) {
+        self.buffer.seal_history();
+        self.clear_selection();
+        self.text_location = self.location_at(position);
+        self.scroll_location_into_view();
+    }
+
+/// Moves the cursor to `position`, selecting from where the drag started.
+    pub fn drag_to(&mut self, position: Position) {
+        self.selection_anchor.get_or_insert(self.text_location);
+        self.text_location = self.location_at(position);
+        self.scroll_location_into_view();
+        self.need_redraw = true;
+    }
+
+/// Selects the word at `position`.
+    pub fn select_word_at(&mut self, position: Position) {
+        let Location { grapheme_index, line_index } = self.location_at(position);
+        let word = self.buffer.line(line_index).map_or(0..0, |line| line.word_at(grapheme_index));
+        self.select(
+            Location { grapheme_index: word.start, line_index },
+            Location { grapheme_index: word.end, line_index },
+        );
+    }
+
+/// Selects the line at `position`, including its line break.
+    pub fn select_line_at(&mut self, position: Position) {
+        let line_index = self.location_at(position).line_index;
+        self.select(
+            Location { grapheme_index: 0, line_index },
+            Location { grapheme_index: 0, line_index: line_index.saturating_add(1) },
+        );
+    }
+
+    fn select(&mut self, anchor: Location, cursor: Location) {
+        self.buffer.seal_history();
+        self.selection_anchor = Some(anchor);
+        self.text_location = cursor;
+        self.snap_to_valid_line();
+        self.scroll_location_into_view();
+        self.need_redraw = true;
+    }
+
+/// Scrolls the text up or down by `rows` visual rows, leaving the cursor
+/// where it is.
+    pub fn scroll_rows(&mut self, up: bool, rows: usize) {
+        if self.wrap_width().is_some() {
+            let top = (self.scroll_offset.row, self.scroll_offset.col);
+            let (row, col) = if up { self.rows_before(top, rows) } else { self.rows_after(top, rows) };
+            self.scroll_offset = Position { row, col };
+        } else if up {
+            self.scroll_offset.row = self.scroll_offset.row.saturating_sub(rows);
+        } else {
+            let last_line = self.buffer.height().saturating_sub(1);
+            self.scroll_offset.row = self.scroll_offset.row.saturating_add(rows).min(last_line);
+        }
+        self.need_redraw = true;
+    }
+
     pub fn text_location_to_position(&self) -> Position {
         let row = self.text_location.line_index;
-        let col = self.buffer.lines
-                         .get(row)
+        let col = self.buffer
+                         .line(row)
                          .map_or(0, |line| {
                             line.width_until(self.text_location.grapheme_index)
                          });
@@ -123,11 +974,19 @@ impl View {
             Direction::Right     =>  self.move_right(),
             Direction::Home      =>  self.move_home(),
             Direction::End       =>  self.move_end(),
+            Direction::WordLeft  =>  self.move_word_left(),
+            Direction::WordRight =>  self.move_word_right(),
+            Direction::DocumentStart => self.text_location = Location::default(),
+            Direction::DocumentEnd   => self.move_document_end(),
         }
         self.scroll_location_into_view()
     }
 
     fn move_up(&mut self) {
+        if self.wrap_width().is_some() {
+            self.move_by_row(false);
+            return ;
+        }
         self.text_location.line_index = self
             .text_location
             .line_index
@@ -136,6 +995,10 @@ impl View {
     }
 
     fn move_down(&mut self) {
+        if self.wrap_width().is_some() {
+            self.move_by_row(true);
+            return ;
+        }
         self.text_location.line_index = self
             .text_location
             .line_index
@@ -146,6 +1009,12 @@ impl View {
 
     fn move_page_up(&mut self) {
         let Size{ height, .. } = self.size;
+        if self.wrap_width().is_some() {
+            for _ in 0..height {
+                self.move_by_row(false);
+            }
+            return ;
+        }
         self.text_location.line_index = self
             .text_location
             .line_index
@@ -155,6 +1024,12 @@ impl View {
 
     fn move_page_down(&mut self) {
         let Size{ height, .. } = self.size;
+        if self.wrap_width().is_some() {
+            for _ in 0..height {
+                self.move_by_row(true);
+            }
+            return ;
+        }
         self.text_location.line_index = self
             .text_location
             .line_index
@@ -163,6 +1038,47 @@ impl View {
         self.snap_to_valid_line();
     }
 
+    // Moves the cursor to the visual row above or below, keeping its column
+    // within the row.
+    fn move_by_row(&mut self, down: bool) {
+        let (line_index, row) = self.cursor_row();
+        let starts = self.row_starts(line_index);
+        let line = self.buffer.line(line_index).unwrap_or_default();
+        let column = line
+            .width_until(self.text_location.grapheme_index)
+            .saturating_sub(line.width_until(starts[row]));
+
+        let (target_line, target_row) = if down {
+            if row.saturating_add(1) < starts.len() {
+                (line_index, row.saturating_add(1))
+            } else if line_index < self.buffer.height() {
+                (line_index.saturating_add(1), 0)
+            } else {
+                return ;
+            }
+        } else if row > 0 {
+            (line_index, row.saturating_sub(1))
+        } else if line_index > 0 {
+            let previous = line_index.saturating_sub(1);
+            (previous, self.row_starts(previous).len().saturating_sub(1))
+        } else {
+            return ;
+        };
+
+        let target_starts = self.row_starts(target_line);
+        let target = self.buffer.line(target_line).unwrap_or_default();
+        let start = target_starts[target_row];
+        // The cursor can't be put after the last grapheme of a row that
+        // continues, as that spot is the start of the next row.
+        let end = target_starts
+            .get(target_row.saturating_add(1))
+            .map_or(target.grapheme_count(), |next| next.saturating_sub(1));
+        self.text_location = Location {
+            line_index: target_line,
+            grapheme_index: target.grapheme_at_column(start..end, column),
+        };
+    }
+
     fn move_left(&mut self) {
         if self.text_location.grapheme_index == 0 {
             if self.text_location.line_index == 0 {
@@ -178,9 +1094,7 @@ impl View {
     fn move_right(&mut self) {
         let line_len = self
             .buffer
-            .lines
-            .get(self.text_location.line_index)
-            .map_or(0, |line| line.len());
+            .grapheme_count(self.text_location.line_index);
         if self.text_location.grapheme_index >= line_len {
             self.move_down();
             self.move_home();
@@ -189,6 +1103,36 @@ impl View {
         }
     }
 
+    // Moves to the start of the word before the cursor, or to the end of the
+    // line above from the start of a line.
+    fn move_word_left(&mut self) {
+        let Location { grapheme_index, line_index } = self.text_location;
+        let start = self.buffer.line(line_index).and_then(|line| line.previous_word_start(grapheme_index));
+        match start {
+            Some(start) => self.text_location.grapheme_index = start,
+            None => self.move_left(),
+        }
+    }
+
+    // Moves to the end of the word after the cursor, or to the start of the
+    // line below from the end of a line.
+    fn move_word_right(&mut self) {
+        let Location { grapheme_index, line_index } = self.text_location;
+        let end = self.buffer.line(line_index).and_then(|line| line.next_word_end(grapheme_index));
+        match end {
+            Some(end) => self.text_location.grapheme_index = end,
+            None => self.move_right(),
+        }
+    }
+
+    fn move_document_end(&mut self) {
+        let line_index = self.buffer.height().saturating_sub(1);
+        self.text_location = Location {
+            grapheme_index: self.buffer.grapheme_count(line_index),
+            line_index,
+        };
+    }
+
     fn move_home(&mut self) {
         self.text_location.grapheme_index = 0;
     }
@@ -196,25 +1140,17 @@ impl View {
     fn move_end(&mut self) {
         let line_len = self
             .buffer
-            .lines
-            .get(self.text_location.line_index)
-            .map_or(0, |line| line.len());
+            .grapheme_count(self.text_location.line_index);
         self.text_location.grapheme_index = line_len;
     }
 
     // Ensures self.location.grapheme_index points to a valid grapheme index by snapping it to the left most grapheme if appropriate.
     // Doesn't trigger scrolling.
     fn snap_to_valid_grapheme(&mut self) {
-        self.text_location.grapheme_index = self
-            .buffer
-            .lines
-            .get(self.text_location.line_index)
-            .map_or(0, |line| {
-                std::cmp::min(
-                    line.grapheme_count(),
-                    self.text_location.grapheme_index,
-                )
-            });
+        self.text_location.grapheme_index = std::cmp::min(
+            self.buffer.grapheme_count(self.text_location.line_index),
+            self.text_location.grapheme_index,
+        );
     }
     // Ensures self.location.line_index points to a valid line index by snapping it to the bottom most line if appropriate.
     // Doesn't trigger scrolling.
@@ -231,6 +1167,27 @@ impl View {
         self.need_redraw = true;
     }
 
+    // Scrolls by visual rows so that the cursor's row is on screen.
+    fn scroll_wrapped_into_view(&mut self) {
+        let Size { height, .. } = self.size;
+        let top_rows = self.row_starts(self.scroll_offset.row).len();
+        if self.scroll_offset.col >= top_rows {
+            // The top line got fewer rows since it was scrolled to.
+            self.scroll_offset.col = top_rows.saturating_sub(1);
+            self.need_redraw = true;
+        }
+        let top = (self.scroll_offset.row, self.scroll_offset.col);
+        let cursor = self.cursor_row();
+        if cursor < top {
+            self.scroll_offset = Position { row: cursor.0, col: cursor.1 };
+            self.need_redraw = true;
+        } else if self.rows_between(top, cursor, height) >= height {
+            let (row, col) = self.rows_before(cursor, height.saturating_sub(1));
+            self.scroll_offset = Position { row, col };
+            self.need_redraw = true;
+        }
+    }
+
     fn scroll_vertically(&mut self, to: usize) {
         let Size { height, .. } = self.size;
         if to < self.scroll_offset.row {
@@ -243,7 +1200,7 @@ impl View {
     }
 
     fn scroll_horizontally(&mut self, to: usize) {
-        let Size { width, .. } = self.size;
+        let width = self.text_width();
         if to < self.scroll_offset.col {
             self.scroll_offset.col = to;
             self.need_redraw = true
@@ -254,32 +1211,67 @@ impl View {
     }
 
     pub fn scroll_location_into_view(&mut self) {
+        if self.wrap_width().is_some() {
+            self.scroll_wrapped_into_view();
+            return ;
+        }
         let Position { row, col } = self.text_location_to_position();
 
         self.scroll_vertically(row);
         self.scroll_horizontally(col);
     }
 
-    pub fn cursor_position(&self) -> Position {
-        self.text_location_to_position()
-            .saturating_sub(self.scroll_offset)
+/// Where the cursor is drawn, or None if the text was scrolled away from it.
+    pub fn cursor_position(&self) -> Option<Position> {
+        let Position { row, col } = if self.wrap_width().is_some() {
+            if self.cursor_row() < (self.scroll_offset.row, self.scroll_offset.col) {
+                return None;
+            }
+            self.wrapped_cursor_position()
+        } else {
+            let position = self.text_location_to_position();
+            if position.row < self.scroll_offset.row {
+                return None;
+            }
+            position.saturating_sub(self.scroll_offset)
+        };
+        if row >= self.size.height {
+            return None;
+        }
+        Some(Position { row, col: col.saturating_add(self.gutter_width()) })
+    }
+
+    // The cursor's place in the text area when lines are wrapped. On
+    // whitespace hanging past the edge it stays at the last column.
+    fn wrapped_cursor_position(&self) -> Position {
+        let (line_index, row) = self.cursor_row();
+        let starts = self.row_starts(line_index);
+        let line = self.buffer.line(line_index).unwrap_or_default();
+        let col = line
+            .width_until(self.text_location.grapheme_index)
+            .saturating_sub(line.width_until(starts[row]));
+        let top = (self.scroll_offset.row, self.scroll_offset.col);
+        Position {
+            row: self.rows_between(top, (line_index, row), usize::MAX),
+            col: col.min(self.text_width().saturating_sub(1)),
+        }
     }
 
 
     pub fn insert_character(&mut self, character: char) {
+        self.replace_selection(|view| view.insert_character_at_cursor(character));
+    }
+
+    fn insert_character_at_cursor(&mut self, character: char) {
         let old_len = self
             .buffer
-            .lines
-            .get(self.text_location.line_index)
-            .map_or(0, Line::grapheme_count);
+            .grapheme_count(self.text_location.line_index);
 
         self.buffer.insert_char(character, self.text_location);
         
         let new_len = self
             .buffer
-            .lines
-            .get(self.text_location.line_index)
-            .map_or(0, Line::grapheme_count);
+            .grapheme_count(self.text_location.line_index);
 
         let grapheme_delta = new_len.saturating_sub(old_len);
         if grapheme_delta > 0 {
@@ -290,6 +1282,9 @@ impl View {
     }
 
     pub fn backspace(&mut self) {
+        if self.delete_selection() {
+            return ;
+        }
         if self.text_location.line_index     == 0 && 
            self.text_location.grapheme_index == 0 {
             return ;
@@ -299,28 +1294,91 @@ impl View {
     }
 
     pub fn delete(&mut self) {
+        if self.delete_selection() {
+            return ;
+        }
         self.buffer.delete_char(self.text_location);
         self.need_redraw = true;
     }
 
-    pub fn insert_tab(&mut self) {
-        let tab_size = 4;
-        for _ in 0..tab_size {
-            self.buffer.insert_char(' ', self.text_location);
-            self.text_location.grapheme_index += 1;    
+/// Deletes from the cursor back to the start of the word before it.
+    pub fn delete_word_backward(&mut self) {
+        if self.delete_selection() {
+            return ;
         }
+        let end = self.text_location;
+        self.move_word_left();
+        self.buffer.delete_range(self.text_location, end);
+        self.scroll_location_into_view();
+        self.need_redraw = true;
+    }
+
+/// Deletes from the cursor to the end of the word after it.
+    pub fn delete_word_forward(&mut self) {
+        if self.delete_selection() {
+            return ;
+        }
+        let start = self.text_location;
+        self.move_word_right();
+        let end = std::mem::replace(&mut self.text_location, start);
+        self.buffer.delete_range(start, end);
+        self.need_redraw = true;
+    }
+
+    pub fn insert_tab(&mut self) {
+        self.replace_selection(|view| {
+            let tab_size = 4;
+            for _ in 0..tab_size {
+                view.buffer.insert_char(' ', view.text_location);
+                view.text_location.grapheme_index += 1;
+            }
+        });
         self.need_redraw = true;
     }
 
     pub fn insert_newline(&mut self) {
-        self.buffer.insert_newline(self.text_location);
-        self.move_text_location(&Direction::Right);
+        self.replace_selection(|view| {
+            view.buffer.insert_newline(view.text_location);
+            view.move_text_location(&Direction::Right);
+        });
         self.need_redraw = true;
     }
 
-    pub fn save(&mut self) {
-        if let Err(err) = self.buffer.save() {
-            eprintln!("Fail to Save: {err}");
+    pub fn undo(&mut self) {
+        if let Some(location) = self.buffer.undo() {
+            self.move_to_edit(location);
         }
     }
+
+    pub fn redo(&mut self) {
+        if let Some(location) = self.buffer.redo() {
+            self.move_to_edit(location);
+        }
+    }
+
+    // Puts the cursor where an undone or redone edit happened.
+    fn move_to_edit(&mut self, location: Location) {
+        self.clear_selection();
+        self.text_location = location;
+        self.snap_to_valid_line();
+        self.snap_to_valid_grapheme();
+        self.scroll_location_into_view();
+        self.need_redraw = true;
+    }
+
+    pub fn save(&mut self) -> Result<(), Box<dyn Error>> {
+        self.buffer.save()
+    }
+
+    pub fn save_as(&mut self, file_name: &str) -> Result<(), Box<dyn Error>> {
+        self.buffer.save_as(file_name)
+    }
+
+    pub fn is_file_loaded(&self) -> bool {
+        self.buffer.file_name().is_some()
+    }
+
+    pub const fn is_modified(&self) -> bool {
+        self.buffer.is_modified()
+    }
 }
\ No newline at end of file
@@ -1,43 +1,172 @@
 use crossterm::event::{
-    Event, KeyEvent, KeyEventKind, read
+    Event, KeyEvent, KeyEventKind, MouseButton, MouseEvent, MouseEventKind
 };
 
 /// The main text editor structure,
 /// responsible for managing the editor state and user interactions.
 mod core;
-use core::{ Terminal, EditorCommand, View, Position };
+use core::{
+    EditorCommand, Direction, View, StatusBar, MessageBar, Prompt, PromptEvent, Pattern,
+    ProjectSearch, Theme, ColorSupport, Clipboard, ClipboardCommand, DEFAULT_REGISTER
+};
+pub use core::{ Backend, Cell, Frame, Position, Script, Size, Terminal, TestBackend };
 
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::error::Error;
+use std::time::{ Duration, Instant };
+
+// How many times Quit has to be pressed in a row to discard unsaved changes.
+const QUIT_TIMES: u8 = 3;
+
+// How long `run` waits for input before redrawing, so expired messages disappear.
+const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// Polled more often while a project search runs, so its results show up promptly.
+const SEARCH_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// How soon another click on the same spot counts as a double or triple click.
+const MULTI_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+
+// How many rows a turn of the mouse wheel scrolls.
+const WHEEL_ROWS: usize = 3;
+
+/// What the open prompt is asking for.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum PromptType {
+    GoToLine,
+    SaveAs,
+    Search,
+    ReplaceFind,
+    ReplaceWith,
+    ReplaceConfirm,
+    ProjectSearch,
+}
+
+impl PromptType {
+    const fn label(self, is_regex: bool) -> &'static str {
+        match (self, is_regex) {
+            (Self::GoToLine, _) => "Go to line: ",
+            (Self::SaveAs, _) => "Save as: ",
+            (Self::Search, false) => "Search (Up/Down for previous/next, Alt+R for regex): ",
+            (Self::Search, true) => "Regex search (Up/Down for previous/next, Alt+R for plain): ",
+            (Self::ReplaceFind, false) => "Replace (Alt+R for regex): ",
+            (Self::ReplaceFind, true) => "Replace regex (Alt+R for plain): ",
+            (Self::ReplaceWith, _) => "Replace with: ",
+            (Self::ReplaceConfirm, _) => "Replace? (y)es, (n)o, (a)ll, (o)nly this one, (q)uit: ",
+            (Self::ProjectSearch, false) => "Search in project (Alt+R for regex): ",
+            (Self::ProjectSearch, true) => "Regex search in project (Alt+R for plain): ",
+        }
+    }
+}
 
 /// Represents the main text editor.
+///
+/// It draws to and reads input from `B`, the real terminal unless another
+/// backend is given.
 #[derive(Default)]
-pub struct Editor{
+pub struct Editor<B: Backend = Terminal> {
+    backend: B,
     should_quit: bool,
+    // Quit presses so far while the document has unsaved changes.
+    quit_times: u8,
 
     // pub cursor: Cursor,
-    pub view: View
+    pub view: View,
+    status_bar: StatusBar,
+    message_bar: MessageBar,
+    prompt: Option<(PromptType, Prompt)>,
+    // Earlier submitted values, offered again by each kind of prompt.
+    prompt_history: HashMap<PromptType, Vec<String>>,
+    // Whether search and replace treat their input as a regular expression.
+    regex_mode: bool,
+    // What the replace prompt is looking for, kept while asking for the replacement.
+    replace_query: String,
+    // The results of the last project search, shown over the text while open.
+    project_search: Option<ProjectSearch>,
+    // Every theme that could be loaded, and the one in use.
+    themes: Vec<Theme>,
+    theme: Theme,
+    // The back buffer everything is drawn into, and what is on screen now.
+    frame: Frame,
+    drawn_frame: Option<Frame>,
+    terminal_size: Size,
+    clipboard: Clipboard,
+    // The register picked for the next cut, copy or paste.
+    register: Option<char>,
+    // Whether the next key names a register.
+    awaiting_register: bool,
+    // When and where the left button was last pressed, and how many clicks
+    // in a row that made.
+    last_click: Option<(Instant, Position, u8)>,
 }
 
 impl Editor {
-/// Creates a new instance of the `Editor`.
+/// Creates a new instance of the `Editor` in the terminal, opening the
+/// file named on the command line.
     pub fn new() -> Result<Self, Box<dyn Error>> {
-        let current_hook = std::panic::take_hook();
-        std::panic::set_hook(Box::new(move |panic_info| {
-            let _ = Terminal::terminate();
-            current_hook(panic_info);
-        }));
+        Self::with_backend(Terminal, std::env::args().skip(1))
+    }
+}
+
+impl<B: Backend> Editor<B> {
+/// Creates an editor that runs on `backend`, taking its options and the
+/// file to open from `args`.
+    pub fn with_backend(backend: B, args: impl IntoIterator<Item = String>) -> Result<Self, Box<dyn Error>>
+    where
+        B: Default,
+    {
+        let mut editor = Self::default();
+        editor.backend = backend;
+        editor.backend.initialize()?;
+        let size = editor.backend.size().unwrap_or_default();
+        editor.resize(size);
 
-        Terminal::initialize()?;
+        let (themes, errors) = Theme::load_all(ColorSupport::detect());
+        if let Some(theme) = themes.first() {
+            editor.theme = theme.clone();
+        }
+        editor.themes = themes;
+        if let Some(error) = errors.first() {
+            editor.message_bar.update_message(&format!("Could not load theme {error}"));
+        }
 
-        let mut view = View::default();
-        let args: Vec<String> = std::env::args().collect();
-        if let Some(file_name) = args.get(1) {
-            view.load(file_name);
+        let mut args = args.into_iter();
+        let mut file_name = None;
+        while let Some(arg) = args.next() {
+            if arg == "--wrap-column" {
+                let column = args.next().unwrap_or_default();
+                match column.parse::<usize>() {
+                    Ok(column) if column > 0 => editor.view.set_wrap_column(column),
+                    _ => editor.message_bar.update_message(&format!("Invalid wrap column: {column}")),
+                }
+            } else if arg == "--clipboard" {
+                let name = args.next().unwrap_or_default();
+                match ClipboardCommand::from_name(&name) {
+                    Some(command) => editor.clipboard.set_command(command),
+                    None => editor.message_bar.update_message(&format!("Unknown clipboard command: {name}")),
+                }
+            } else if arg == "--undo-dir" {
+                match args.next() {
+                    Some(dir) => editor.view.set_undo_dir(Some(PathBuf::from(dir))),
+                    None => editor.message_bar.update_message("--undo-dir needs a directory"),
+                }
+            } else if file_name.is_none() {
+                file_name = Some(arg);
+            }
+        }
+        if let Some(file_name) = file_name && let Err(err) = editor.view.load(&file_name) {
+            editor.message_bar.update_message(&format!("Could not open {file_name}: {err}"));
         }
 
-        Ok(Self { should_quit: false, view })
+        Ok(editor)
     }
-    
+
+/// The backend the editor runs on, e.g. to look at what a `TestBackend` shows.
+    pub const fn backend(&self) -> &B {
+        &self.backend
+    }
+
 /// The Read-Eval-Print Loop (REPL) for the editor.
     pub fn run(&mut self) {
         loop {
@@ -46,14 +175,23 @@ impl Editor {
             // Read an event
             // Evaluate the event
 
+            if let Some(project_search) = &mut self.project_search {
+                project_search.poll();
+            }
             self.refresh_screen();
-            if self.should_quit {
+            if self.should_quit || self.backend.is_input_closed() {
                 break;
             }
-            match read() {
-                Ok(event) => self.evaluate_event(&event),
+            let interval = if self.project_search.as_ref().is_some_and(ProjectSearch::is_running) {
+                SEARCH_POLL_INTERVAL
+            } else {
+                EVENT_POLL_INTERVAL
+            };
+            match self.backend.poll_event(interval) {
+                Ok(None) => (),
+                Ok(Some(event)) => self.evaluate_event(&event),
                 Err(err) => {
-                    eprint!("Could not read event: {err:?}");
+                    self.message_bar.update_message(&format!("Could not read event: {err}"));
                 }
             }
         }
@@ -61,52 +199,490 @@ impl Editor {
 
 /// Refreshes the terminal screen based on the current editor state.
     fn refresh_screen(&mut self) {
-        // Hide the cursor to prevent flickering during updates
-        let _ = Terminal::hide_caret();
-        let _ = Terminal::move_cursor_to(Position::default());
-
-        // Check if we should quit
         if self.should_quit {
-            let _ = Terminal::clear_screen();
-            let _ = Terminal::print("Goodbye!\r\n");
+            return ;
+        }
+        // Draw the rows into the back buffer, then send what changed
+        let bottom_row = self.terminal_size.height.saturating_sub(1);
+        let frame = &mut self.frame;
+        if let Some(project_search) = &mut self.project_search {
+            project_search.render(frame, &self.theme);
+        } else {
+            self.view.render(frame, &self.theme);
+        }
+        self.status_bar.update_status(self.view.get_status());
+        self.status_bar.render(frame, bottom_row.saturating_sub(1), &self.theme);
+        let cursor = if let Some((_, prompt)) = &self.prompt {
+            prompt.render(frame, bottom_row, &self.theme);
+            Some(Position::new(bottom_row, prompt.caret_column()))
         } else {
-            // Draw the rows
-            let _ = self.view.render();
-            // Self::draw_version()?;
-            let _ = Terminal::move_cursor_to(self.view.cursor_position());
+            self.message_bar.render(frame, bottom_row, &self.theme);
+            if self.project_search.is_some() {
+                Some(Position::default())
+            } else {
+                self.view.cursor_position()
+            }
+        };
+
+        if let Err(err) = self.backend.draw(&self.frame, self.drawn_frame.as_ref(), cursor) {
+            // Part of the frame may have reached the terminal, so the next
+            // one is drawn from scratch, with the error in the message bar.
+            self.message_bar.update_message(&format!("Could not draw the screen: {err}"));
+            self.drawn_frame = None;
+            return ;
         }
+        match &mut self.drawn_frame {
+            Some(drawn_frame) => drawn_frame.clone_from(&self.frame),
+            None => self.drawn_frame = Some(self.frame.clone()),
+        }
+    }
 
-        // Show the cursor again after updates
-        // Execute all terminal commands
-        let _ = Terminal::show_caret();
-        let _ = Terminal::execute();
+/// Resizes the editor, keeping the two bottom rows for the status bar and messages.
+    fn resize(&mut self, size: Size) {
+        self.terminal_size = size;
+        // What the terminal shows after a resize is unknown, so the next
+        // frame is drawn from scratch.
+        self.frame = Frame::new(size);
+        self.drawn_frame = None;
+        let text_area = Size {
+            height: size.height.saturating_sub(2),
+            width: size.width,
+        };
+        let _ = self.view.handle_command(EditorCommand::Resize(text_area));
+        if let Some(project_search) = &mut self.project_search {
+            project_search.resize(text_area);
+        }
+        self.status_bar.resize(size);
+        self.message_bar.resize(size);
+        if let Some((_, prompt)) = &mut self.prompt {
+            prompt.resize(size);
+        }
     }
 
 /// Evaluates a key event and updates the editor state accordingly.
     fn evaluate_event(&mut self, event: &Event) {
+        if let Event::Paste(text) = event {
+            self.paste_text(text);
+            return ;
+        }
+        if let Event::Mouse(mouse_event) = event {
+            self.handle_mouse(mouse_event);
+            return ;
+        }
         let should_process = match event {
             Event::Key(KeyEvent { kind, .. }) =>
                 kind == &KeyEventKind::Press,
             Event::Resize(_, _) => true,
             _ => false,
         };
-        if should_process {
-            if let Ok(command) = EditorCommand::try_from(event) {
-                if command == EditorCommand::Quit {
-                    self.should_quit = true;
-                } else {
-                    self.view.handle_command(command);
+        if should_process && let Ok(command) = EditorCommand::try_from(event) {
+            if let EditorCommand::Resize(size) = command {
+                self.resize(size);
+            } else if self.awaiting_register {
+                self.pick_register(&command);
+            } else if self.prompt.is_some() {
+                self.handle_prompt_command(&command);
+            } else if self.project_search.is_some() {
+                self.handle_results_command(command);
+            } else {
+                self.handle_command(command);
+            }
+        }
+    }
+
+// Inserts text the terminal pasted all at once, rather than key by key.
+    fn paste_text(&mut self, text: &str) {
+        if self.awaiting_register {
+            self.awaiting_register = false;
+            self.message_bar.update_message("");
+        }
+        match &self.prompt {
+            // Its keys are answers, not text.
+            Some((PromptType::ReplaceConfirm, _)) => (),
+            Some(_) => {
+                // A prompt only takes a single line.
+                for character in text.chars().take_while(|character| !matches!(character, '\n' | '\r')) {
+                    self.handle_prompt_command(&EditorCommand::Insert(character));
+                }
+            }
+            None if self.project_search.is_none() => {
+                self.reset_quit_times();
+                self.view.paste(text);
+            }
+            None => (),
+        }
+    }
+
+// Clicks place the cursor, dragging selects and the wheel scrolls. A
+// double click selects a word and a triple click a line.
+    fn handle_mouse(&mut self, event: &MouseEvent) {
+        if self.prompt.is_some() || self.project_search.is_some() {
+            return ;
+        }
+        let text_height = self.terminal_size.height.saturating_sub(2);
+        let position = Position::new(
+            usize::from(event.row).min(text_height.saturating_sub(1)),
+            usize::from(event.column),
+        );
+        match event.kind {
+            MouseEventKind::Down(MouseButton::Left) if usize::from(event.row) < text_height => {
+                self.reset_quit_times();
+                match self.count_click(position) {
+                    1 => self.view.click(position),
+                    2 => self.view.select_word_at(position),
+                    _ => self.view.select_line_at(position),
+                }
+            }
+            MouseEventKind::Drag(MouseButton::Left) => self.view.drag_to(position),
+            MouseEventKind::ScrollUp => self.view.scroll_rows(true, WHEEL_ROWS),
+            MouseEventKind::ScrollDown => self.view.scroll_rows(false, WHEEL_ROWS),
+            _ => (),
+        }
+    }
+
+// Counts a click at `position`: 1, or 2 and then 3 for quick clicks on the
+// same spot, after which it starts over.
+    fn count_click(&mut self, position: Position) -> u8 {
+        let now = Instant::now();
+        let clicks = match self.last_click {
+            Some((time, last_position, clicks)) if last_position == position &&
+                now.duration_since(time) <= MULTI_CLICK_INTERVAL => clicks % 3 + 1,
+            _ => 1,
+        };
+        self.last_click = Some((now, position, clicks));
+        clicks
+    }
+
+    fn handle_command(&mut self, command: EditorCommand) {
+        if command != EditorCommand::Quit {
+            self.reset_quit_times();
+        }
+        match command {
+            EditorCommand::Quit => self.handle_quit(),
+            EditorCommand::Save => self.handle_save(),
+            EditorCommand::GoToLine => self.open_prompt(PromptType::GoToLine),
+            EditorCommand::Search => {
+                self.view.enter_search();
+                self.open_prompt(PromptType::Search);
+            }
+            EditorCommand::Replace => self.open_prompt(PromptType::ReplaceFind),
+            EditorCommand::ProjectSearch => self.open_prompt(PromptType::ProjectSearch),
+            EditorCommand::CycleTheme => self.cycle_theme(),
+            EditorCommand::Cut => self.cut(),
+            EditorCommand::Copy => self.copy(),
+            EditorCommand::Paste => self.paste(),
+            EditorCommand::SelectRegister => {
+                self.awaiting_register = true;
+                self.message_bar.update_message("Register: ");
+            }
+            command => {
+                if let Err(err) = self.view.handle_command(command) {
+                    self.message_bar.update_message(&format!("Error: {err}"));
+                }
+            }
+        }
+    }
+
+// Quits right away unless there are unsaved changes, which need
+// `QUIT_TIMES` presses in a row to throw away.
+    fn handle_quit(&mut self) {
+        if !self.view.is_modified() || self.quit_times.saturating_add(1) >= QUIT_TIMES {
+            self.should_quit = true;
+            return ;
+        }
+        self.quit_times = self.quit_times.saturating_add(1);
+        let remaining = QUIT_TIMES.saturating_sub(self.quit_times);
+        self.message_bar.update_message(&format!(
+            "WARNING! File has unsaved changes. Press Ctrl+Q {remaining} more times to quit."
+        ));
+    }
+
+    fn reset_quit_times(&mut self) {
+        if self.quit_times > 0 {
+            self.quit_times = 0;
+            self.message_bar.update_message("");
+        }
+    }
+
+    // Takes the key pressed after SelectRegister as the register's name.
+    fn pick_register(&mut self, command: &EditorCommand) {
+        self.awaiting_register = false;
+        if let EditorCommand::Insert(name) = command {
+            self.register = Some(*name);
+            self.message_bar.update_message(&format!("Register {name}"));
+        } else {
+            self.message_bar.update_message("");
+        }
+    }
+
+    fn cut(&mut self) {
+        let register = self.register.take().unwrap_or(DEFAULT_REGISTER);
+        if let Some(text) = self.view.cut_selection() {
+            self.store(register, &text);
+        }
+    }
+
+    fn copy(&mut self) {
+        let register = self.register.take().unwrap_or(DEFAULT_REGISTER);
+        if let Some(text) = self.view.selected_text() {
+            self.store(register, &text);
+        }
+    }
+
+    // Puts cut or copied text into a register; the default one also goes to
+    // the system clipboard.
+    fn store(&mut self, register: char, text: &str) {
+        if let Err(err) = self.clipboard.copy(register, text) {
+            self.message_bar.update_message(&format!("Could not copy to the clipboard: {err}"));
+            return ;
+        }
+        if register == DEFAULT_REGISTER && !self.clipboard.has_command() &&
+           let Err(err) = self.backend.set_clipboard(text) {
+            self.message_bar.update_message(&format!("Could not copy to the clipboard: {err}"));
+        }
+    }
+
+    fn paste(&mut self) {
+        let register = self.register.take().unwrap_or(DEFAULT_REGISTER);
+        match self.clipboard.paste(register) {
+            Ok(Some(text)) => self.view.paste(&text),
+            Ok(None) => self.message_bar.update_message(&format!("Register {register} is empty")),
+            Err(err) => self.message_bar.update_message(&format!("Could not paste: {err}")),
+        }
+    }
+
+    fn cycle_theme(&mut self) {
+        let current = self.themes.iter().position(|theme| theme.name() == self.theme.name());
+        let next = current.map_or(0, |index| index.saturating_add(1) % self.themes.len());
+        let Some(theme) = self.themes.get(next) else {
+            return ;
+        };
+        self.theme = theme.clone();
+        self.view.mark_redraw();
+        self.status_bar.mark_redraw();
+        if let Some(project_search) = &mut self.project_search {
+            project_search.mark_redraw();
+        }
+        self.message_bar.update_message(&format!("Theme: {}", self.theme.name()));
+    }
+
+    fn handle_save(&mut self) {
+        if self.view.is_file_loaded() {
+            let result = self.view.save();
+            self.report_save(result);
+        } else {
+            self.open_prompt(PromptType::SaveAs);
+        }
+    }
+
+    fn report_save(&mut self, result: Result<(), Box<dyn Error>>) {
+        match result {
+            Ok(()) => self.message_bar.update_message("File saved successfully."),
+            Err(err) => self.message_bar.update_message(&format!("Error writing file: {err}")),
+        }
+    }
+
+    fn open_prompt(&mut self, prompt_type: PromptType) {
+        let history = self.prompt_history.get(&prompt_type).cloned().unwrap_or_default();
+        let prompt = Prompt::new(prompt_type.label(self.regex_mode), history, self.terminal_size);
+        self.prompt = Some((prompt_type, prompt));
+    }
+
+    fn close_prompt(&mut self) {
+        self.prompt = None;
+        self.message_bar.mark_redraw();
+    }
+
+    fn handle_prompt_command(&mut self, command: &EditorCommand) {
+        let Some((prompt_type, prompt)) = &mut self.prompt else {
+            return ;
+        };
+        let prompt_type = *prompt_type;
+        if prompt_type == PromptType::ReplaceConfirm {
+            self.handle_replace_confirm(command);
+            return ;
+        }
+        if *command == EditorCommand::ToggleRegex &&
+           matches!(prompt_type, PromptType::Search | PromptType::ReplaceFind | PromptType::ProjectSearch) {
+            self.regex_mode = !self.regex_mode;
+            prompt.set_label(prompt_type.label(self.regex_mode));
+            if prompt_type == PromptType::Search {
+                self.view.search(prompt.value(), self.regex_mode);
+            }
+            return ;
+        }
+        if prompt_type == PromptType::Search {
+            // Up and Down step through matches instead of the prompt history.
+            match command {
+                EditorCommand::Move(Direction::Down) => {
+                    self.view.search_next();
+                    return ;
+                }
+                EditorCommand::Move(Direction::Up) => {
+                    self.view.search_prev();
+                    return ;
+                }
+                _ => (),
+            }
+        }
+        match prompt.handle_command(command) {
+            PromptEvent::Unchanged => (),
+            PromptEvent::Edited => {
+                if prompt_type == PromptType::Search {
+                    self.view.search(prompt.value(), self.regex_mode);
                 }
             }
+            PromptEvent::Cancelled => {
+                if prompt_type == PromptType::Search {
+                    self.view.dismiss_search();
+                }
+                self.close_prompt();
+            }
+            PromptEvent::Submitted(value) => {
+                self.close_prompt();
+                if !value.is_empty() {
+                    let history = self.prompt_history.entry(prompt_type).or_default();
+                    history.retain(|entry| entry != &value);
+                    history.push(value.clone());
+                }
+                self.submit_prompt(prompt_type, &value);
+            }
+        }
+    }
+
+    fn submit_prompt(&mut self, prompt_type: PromptType, value: &str) {
+        match prompt_type {
+            PromptType::Search => self.view.exit_search(),
+            PromptType::GoToLine => match value.trim().parse::<usize>() {
+                Ok(line_number) => self.view.go_to_line(line_number),
+                Err(_) => self.message_bar.update_message(&format!("Not a line number: {value}")),
+            },
+            PromptType::SaveAs if value.is_empty() => {
+                self.message_bar.update_message("Save aborted.");
+            }
+            PromptType::SaveAs => {
+                let result = self.view.save_as(value);
+                self.report_save(result);
+            }
+            PromptType::ReplaceFind if value.is_empty() => (),
+            PromptType::ReplaceFind => {
+                self.replace_query = value.to_string();
+                self.open_prompt(PromptType::ReplaceWith);
+            }
+            PromptType::ReplaceWith => self.start_replace(value),
+            PromptType::ReplaceConfirm => (),
+            PromptType::ProjectSearch if value.is_empty() => (),
+            PromptType::ProjectSearch => self.start_project_search(value),
+        }
+    }
+
+    fn start_project_search(&mut self, query: &str) {
+        let pattern = match Pattern::new(query, self.regex_mode) {
+            Ok(pattern) => pattern,
+            Err(err) => {
+                self.message_bar.update_message(&format!("Invalid regex: {err}"));
+                return ;
+            }
+        };
+        let root = match std::env::current_dir() {
+            Ok(root) => root,
+            Err(err) => {
+                self.message_bar.update_message(&format!("Could not search project: {err}"));
+                return ;
+            }
+        };
+        let text_area = Size {
+            height: self.terminal_size.height.saturating_sub(2),
+            width: self.terminal_size.width,
+        };
+        self.project_search = Some(ProjectSearch::start(root, query, pattern, text_area));
+    }
+
+// While the results list is open, keys move through it instead of the text.
+    fn handle_results_command(&mut self, command: EditorCommand) {
+        match command {
+            EditorCommand::Move(direction) => {
+                if let Some(project_search) = &mut self.project_search {
+                    project_search.move_selection(&direction);
+                }
+            }
+            EditorCommand::Enter => self.open_selected_match(),
+            EditorCommand::Dismiss => self.close_results(),
+            EditorCommand::Quit | EditorCommand::ProjectSearch | EditorCommand::CycleTheme => {
+                self.handle_command(command);
+            }
+            _ => (),
+        }
+    }
+
+    fn open_selected_match(&mut self) {
+        let Some(found) = self.project_search.as_ref().and_then(ProjectSearch::selected_match) else {
+            return ;
+        };
+        if self.view.is_modified() {
+            self.message_bar.update_message("Save your changes before opening another file.");
+            return ;
+        }
+        let file_name = found.file_name.clone();
+        let location = found.location;
+        match self.view.open_at(&file_name, location) {
+            Ok(()) => self.close_results(),
+            Err(err) => {
+                self.message_bar.update_message(&format!("Could not open {file_name}: {err}"));
+            }
+        }
+    }
+
+    fn close_results(&mut self) {
+        self.project_search = None;
+        self.view.mark_redraw();
+    }
+
+    fn start_replace(&mut self, replacement: &str) {
+        let pattern = match Pattern::new(&self.replace_query, self.regex_mode) {
+            Ok(pattern) => pattern,
+            Err(err) => {
+                self.message_bar.update_message(&format!("Invalid regex: {err}"));
+                return ;
+            }
+        };
+        if self.view.start_replace(pattern, replacement) {
+            self.open_prompt(PromptType::ReplaceConfirm);
+        } else {
+            self.view.end_replace();
+            self.message_bar.update_message("No match found.");
+        }
+    }
+
+// Answers to the replace confirmation are single keys that act right away.
+    fn handle_replace_confirm(&mut self, command: &EditorCommand) {
+        let has_next = match command {
+            EditorCommand::Insert('y' | 'Y') => self.view.replace_current(),
+            EditorCommand::Insert('n' | 'N') => self.view.skip_current(),
+            EditorCommand::Insert('a' | 'A') => {
+                self.view.replace_all_remaining();
+                false
+            }
+            EditorCommand::Insert('o' | 'O') => {
+                self.view.replace_current();
+                false
+            }
+            EditorCommand::Insert('q' | 'Q') | EditorCommand::Dismiss => false,
+            _ => return,
+        };
+        if !has_next {
+            self.close_prompt();
+            let replaced = self.view.end_replace();
+            self.message_bar.update_message(&format!("Replaced {replaced} occurrence(s)."));
         }
     }
 }
 
-impl Drop for Editor {
+impl<B: Backend> Drop for Editor<B> {
     fn drop(&mut self) {
-        let _ = Terminal::terminate();
+        let _ = self.backend.terminate();
         if self.should_quit {
-            let _ = Terminal::print("Goodbye.\r\n");
+            let _ = self.backend.print_line("Goodbye.");
         }
     }
-}
\ No newline at end of file
+}
@@ -1,10 +1,23 @@
 #![warn(clippy::all, clippy::pedantic)]
-use zen_quill::Editor;
+use zen_quill::{ Editor, Script };
 
 fn main() {
-    match Editor::new() {
-        Ok(mut editor) => editor.run(),
-        Err(err) =>
-            eprintln!("Application error: {err}"),
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match Script::from_args(&args) {
+        Ok(Some(script)) => {
+            if let Err(err) = script.run() {
+                eprintln!("Script error: {err}");
+                std::process::exit(1);
+            }
+        }
+        Ok(None) => match Editor::new() {
+            Ok(mut editor) => editor.run(),
+            Err(err) =>
+                eprintln!("Application error: {err}"),
+        },
+        Err(err) => {
+            eprintln!("Application error: {err}");
+            std::process::exit(1);
+        }
     }
 }
\ No newline at end of file
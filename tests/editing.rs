@@ -0,0 +1,295 @@
+use std::fs;
+use std::path::{ Path, PathBuf };
+
+use crossterm::event::{ Event, KeyCode, KeyModifiers, MouseButton, MouseEvent, MouseEventKind };
+use zen_quill::{ Editor, Size, TestBackend };
+
+// A fresh file in the temporary directory, named after the test.
+fn temp_file(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("zen_quill_{}_{name}.txt", std::process::id()));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+fn mouse(kind: MouseEventKind, column: u16, row: u16) -> Event {
+    Event::Mouse(MouseEvent { kind, column, row, modifiers: KeyModifiers::NONE })
+}
+
+// Where the undo history of the file at `path` is kept, so tests stay out
+// of the home directory.
+fn undo_dir(path: &Path) -> PathBuf {
+    path.with_extension("undo")
+}
+
+// Removes a file made by `temp_file` along with its undo history.
+fn remove(path: &Path) {
+    fs::remove_file(path).unwrap();
+    let _ = fs::remove_dir_all(undo_dir(path));
+}
+
+fn run(backend: TestBackend, path: &Path) -> Editor<TestBackend> {
+    let args = [
+        "--undo-dir".to_string(),
+        undo_dir(path).to_string_lossy().into_owned(),
+        path.to_string_lossy().into_owned(),
+    ];
+    let mut editor = Editor::with_backend(backend, args).unwrap();
+    editor.run();
+    editor
+}
+
+#[test]
+fn shows_the_opened_file() {
+    let path = temp_file("open", "first line\nsecond line\n");
+    let editor = run(TestBackend::new(Size { height: 6, width: 120 }), &path);
+    let lines = editor.backend().lines();
+    assert_eq!(lines[..4], ["first line", "second line", "~", "~"]);
+    assert!(lines[4].contains("- 2 lines"));
+    assert_eq!(editor.backend().cursor().row, 0);
+    remove(&path);
+}
+
+#[test]
+fn typing_and_saving() {
+    let path = temp_file("type", "world\n");
+    let mut backend = TestBackend::new(Size { height: 6, width: 40 });
+    backend.push_text("hello\n");
+    backend.push_key(KeyCode::Char('s'), KeyModifiers::CONTROL);
+    let editor = run(backend, &path);
+    assert_eq!(editor.backend().lines()[..3], ["hello", "world", "~"]);
+    assert_eq!(editor.backend().cursor().row, 1);
+    assert_eq!(editor.backend().cursor().col, 0);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "hello\nworld\n");
+    remove(&path);
+}
+
+#[test]
+fn only_changes_are_drawn_over_the_last_frame() {
+    let path = temp_file("wide", "日本語 text\nline two\n");
+    let mut backend = TestBackend::new(Size { height: 5, width: 20 });
+    backend.push_key(KeyCode::Delete, KeyModifiers::NONE);
+    backend.push_key(KeyCode::Down, KeyModifiers::NONE);
+    let editor = run(backend, &path);
+    // The backend applies only the cells that changed, so this also shows
+    // that nothing was missed when the double-width text moved left.
+    assert_eq!(editor.backend().lines()[..2], ["本語 text", "line two"]);
+    assert_eq!(editor.backend().cursor().row, 1);
+    remove(&path);
+}
+
+#[test]
+fn typing_replaces_the_selection() {
+    let path = temp_file("select", "one two\nthree four\n");
+    let mut backend = TestBackend::new(Size { height: 6, width: 40 });
+    for _ in 0..4 {
+        backend.push_key(KeyCode::Right, KeyModifiers::NONE);
+    }
+    backend.push_key(KeyCode::Down, KeyModifiers::SHIFT);
+    backend.push_key(KeyCode::Left, KeyModifiers::SHIFT);
+    backend.push_text("-");
+    backend.push_key(KeyCode::Char('s'), KeyModifiers::CONTROL);
+    let editor = run(backend, &path);
+    assert_eq!(editor.backend().lines()[..2], ["one -ee four", "~"]);
+    assert_eq!(editor.backend().cursor().col, 5);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "one -ee four\n");
+    remove(&path);
+}
+
+#[test]
+fn typing_over_the_selection_is_one_undo_step() {
+    let path = temp_file("select_type_undo", "abc\ndef\n");
+    let mut backend = TestBackend::new(Size { height: 6, width: 40 });
+    for key in [KeyCode::Char('x'), KeyCode::Tab, KeyCode::Enter] {
+        backend.push_key(KeyCode::Right, KeyModifiers::SHIFT);
+        backend.push_key(key, KeyModifiers::NONE);
+        backend.push_key(KeyCode::Char('z'), KeyModifiers::CONTROL);
+    }
+    backend.push_key(KeyCode::Char('s'), KeyModifiers::CONTROL);
+    let editor = run(backend, &path);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "abc\ndef\n");
+    assert_eq!(editor.backend().lines()[..2], ["abc", "def"]);
+    remove(&path);
+}
+
+#[test]
+fn backspace_deletes_the_selection_as_one_step() {
+    let path = temp_file("select_undo", "abc\ndef\n");
+    let mut backend = TestBackend::new(Size { height: 6, width: 40 });
+    backend.push_key(KeyCode::End, KeyModifiers::SHIFT);
+    backend.push_key(KeyCode::Down, KeyModifiers::SHIFT);
+    backend.push_key(KeyCode::Backspace, KeyModifiers::NONE);
+    backend.push_key(KeyCode::Char('s'), KeyModifiers::CONTROL);
+    backend.push_key(KeyCode::Char('z'), KeyModifiers::CONTROL);
+    let editor = run(backend, &path);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "\n");
+    assert_eq!(editor.backend().lines()[..2], ["abc", "def"]);
+    remove(&path);
+}
+
+#[test]
+fn cut_and_paste_move_lines_in_one_step() {
+    let path = temp_file("cut", "one\ntwo\nthree\n");
+    let mut backend = TestBackend::new(Size { height: 6, width: 40 });
+    backend.push_key(KeyCode::Down, KeyModifiers::SHIFT);
+    backend.push_key(KeyCode::Char('x'), KeyModifiers::CONTROL);
+    backend.push_key(KeyCode::Down, KeyModifiers::NONE);
+    backend.push_key(KeyCode::Char('v'), KeyModifiers::CONTROL);
+    backend.push_key(KeyCode::Char('v'), KeyModifiers::CONTROL);
+    backend.push_key(KeyCode::Char('z'), KeyModifiers::CONTROL);
+    backend.push_key(KeyCode::Char('s'), KeyModifiers::CONTROL);
+    let editor = run(backend, &path);
+    assert_eq!(editor.backend().clipboard(), Some("one\n"));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "two\none\nthree\n");
+    assert_eq!(editor.backend().cursor().row, 2);
+    remove(&path);
+}
+
+#[test]
+fn named_registers_stay_off_the_system_clipboard() {
+    let path = temp_file("register", "abc\n");
+    let mut backend = TestBackend::new(Size { height: 6, width: 40 });
+    backend.push_key(KeyCode::Right, KeyModifiers::SHIFT);
+    backend.push_key(KeyCode::Char('\''), KeyModifiers::ALT);
+    backend.push_key(KeyCode::Char('a'), KeyModifiers::NONE);
+    backend.push_key(KeyCode::Char('c'), KeyModifiers::CONTROL);
+    backend.push_key(KeyCode::End, KeyModifiers::NONE);
+    backend.push_key(KeyCode::Char('v'), KeyModifiers::CONTROL);
+    backend.push_key(KeyCode::Char('\''), KeyModifiers::ALT);
+    backend.push_key(KeyCode::Char('a'), KeyModifiers::NONE);
+    backend.push_key(KeyCode::Char('v'), KeyModifiers::CONTROL);
+    let editor = run(backend, &path);
+    assert_eq!(editor.backend().clipboard(), None);
+    assert_eq!(editor.backend().lines()[0], "abca");
+    remove(&path);
+}
+
+#[test]
+fn a_bracketed_paste_is_one_undo_step() {
+    let path = temp_file("paste", "start end\n");
+    let mut backend = TestBackend::new(Size { height: 6, width: 40 });
+    for _ in 0..5 {
+        backend.push_key(KeyCode::Right, KeyModifiers::SHIFT);
+    }
+    backend.push_event(Event::Paste("first\r\n    second\r\n".to_string()));
+    backend.push_key(KeyCode::Char('s'), KeyModifiers::CONTROL);
+    backend.push_key(KeyCode::Char('z'), KeyModifiers::CONTROL);
+    let editor = run(backend, &path);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "first\n    second\n end\n");
+    assert_eq!(editor.backend().lines()[..2], ["start end", "~"]);
+    remove(&path);
+}
+
+#[test]
+fn clicks_land_on_wide_graphemes() {
+    let path = temp_file("click", "日本語 text\nshort\n");
+    let mut backend = TestBackend::new(Size { height: 6, width: 40 });
+    // The right half of 本, then past the end of the second line.
+    backend.push_event(mouse(MouseEventKind::Down(MouseButton::Left), 3, 0));
+    backend.push_text("x");
+    backend.push_event(mouse(MouseEventKind::Down(MouseButton::Left), 30, 1));
+    backend.push_text("y");
+    let editor = run(backend, &path);
+    assert_eq!(editor.backend().lines()[..2], ["日x本語 text", "shorty"]);
+    remove(&path);
+}
+
+#[test]
+fn dragging_and_double_clicking_select() {
+    let path = temp_file("drag", "one two three\nfour five\n");
+    let mut backend = TestBackend::new(Size { height: 6, width: 40 });
+    backend.push_event(mouse(MouseEventKind::Down(MouseButton::Left), 2, 0));
+    backend.push_event(mouse(MouseEventKind::Drag(MouseButton::Left), 2, 1));
+    backend.push_key(KeyCode::Delete, KeyModifiers::NONE);
+    backend.push_event(mouse(MouseEventKind::Down(MouseButton::Left), 6, 0));
+    backend.push_event(mouse(MouseEventKind::Down(MouseButton::Left), 6, 0));
+    backend.push_text("FIVE");
+    let editor = run(backend, &path);
+    assert_eq!(editor.backend().lines()[..2], ["onur FIVE", "~"]);
+    remove(&path);
+}
+
+#[test]
+fn the_wheel_scrolls_without_moving_the_cursor() {
+    let contents: String = (1..=20).map(|number| format!("line {number}\n")).collect();
+    let path = temp_file("wheel", &contents);
+    let mut backend = TestBackend::new(Size { height: 6, width: 40 });
+    backend.push_event(mouse(MouseEventKind::ScrollDown, 0, 0));
+    backend.push_event(mouse(MouseEventKind::ScrollDown, 0, 0));
+    let editor = run(backend, &path);
+    assert_eq!(editor.backend().lines()[0], "line 7");
+    assert!(!editor.backend().is_cursor_visible());
+
+    let mut backend = TestBackend::new(Size { height: 6, width: 40 });
+    backend.push_event(mouse(MouseEventKind::ScrollDown, 0, 0));
+    backend.push_key(KeyCode::Right, KeyModifiers::NONE);
+    let editor = run(backend, &path);
+    assert_eq!(editor.backend().lines()[0], "line 1");
+    assert_eq!(editor.backend().cursor().col, 1);
+    assert!(editor.backend().is_cursor_visible());
+    remove(&path);
+}
+
+#[test]
+fn moving_and_deleting_by_word() {
+    let path = temp_file("words", "let foo.bar  = 日本;\nend\n");
+    let mut backend = TestBackend::new(Size { height: 6, width: 40 });
+    for _ in 0..3 {
+        backend.push_key(KeyCode::Right, KeyModifiers::CONTROL);
+    }
+    backend.push_text("|");
+    backend.push_key(KeyCode::Right, KeyModifiers::CONTROL);
+    backend.push_key(KeyCode::Right, KeyModifiers::CONTROL);
+    backend.push_key(KeyCode::Backspace, KeyModifiers::CONTROL);
+    backend.push_key(KeyCode::Left, KeyModifiers::CONTROL);
+    backend.push_key(KeyCode::Delete, KeyModifiers::CONTROL);
+    backend.push_key(KeyCode::End, KeyModifiers::CONTROL);
+    backend.push_text("!");
+    backend.push_key(KeyCode::Home, KeyModifiers::CONTROL);
+    backend.push_text("#");
+    let editor = run(backend, &path);
+    assert_eq!(editor.backend().lines()[..2], ["#let foo.bar  =| ;", "end!"]);
+    remove(&path);
+}
+
+#[test]
+fn ctrl_h_deletes_a_character_and_alt_backspace_a_word() {
+    let path = temp_file("ctrl_h", "one two three\n");
+    let mut backend = TestBackend::new(Size { height: 6, width: 40 });
+    backend.push_key(KeyCode::End, KeyModifiers::NONE);
+    backend.push_key(KeyCode::Backspace, KeyModifiers::ALT);
+    backend.push_key(KeyCode::Char('h'), KeyModifiers::CONTROL);
+    let editor = run(backend, &path);
+    assert_eq!(editor.backend().lines()[0], "one two");
+    remove(&path);
+}
+
+#[test]
+fn reopening_with_an_encoding_keeps_unsaved_changes() {
+    let path = temp_file("reopen", "text\n");
+    let mut backend = TestBackend::new(Size { height: 6, width: 80 });
+    backend.push_text("x");
+    backend.push_key(KeyCode::Char('o'), KeyModifiers::ALT);
+    let editor = run(backend, &path);
+    let lines = editor.backend().lines();
+    assert_eq!(lines[0], "xtext");
+    assert!(lines[5].contains("save the changes"));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "text\n");
+    remove(&path);
+}
+
+#[cfg(feature = "tree-sitter")]
+#[test]
+fn selecting_nodes_grows_a_selection_that_edits_act_on() {
+    let path = std::env::temp_dir().join(format!("zen_quill_{}_select_node.rs", std::process::id()));
+    fs::write(&path, "fn main() { let value = 1; }\n").unwrap();
+    let mut backend = TestBackend::new(Size { height: 6, width: 40 });
+    for _ in 0..17 {
+        backend.push_key(KeyCode::Right, KeyModifiers::NONE);
+    }
+    backend.push_key(KeyCode::Char('s'), KeyModifiers::ALT);
+    backend.push_key(KeyCode::Char('s'), KeyModifiers::ALT);
+    backend.push_key(KeyCode::Char('x'), KeyModifiers::CONTROL);
+    let editor = run(backend, &path);
+    assert_eq!(editor.backend().lines()[0], "fn main() {  }");
+    remove(&path);
+}
@@ -0,0 +1,94 @@
+use std::fs;
+use std::path::PathBuf;
+
+use zen_quill::Script;
+
+// A fresh file in the temporary directory, named after the test.
+fn temp_file(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("zen_quill_script_{}_{name}.txt", std::process::id()));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+fn args(args: &[&PathBuf]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string_lossy().into_owned()).collect()
+}
+
+#[test]
+fn edits_the_file_in_place() {
+    let file = temp_file("in_place", "one\nthree\n");
+    let script = temp_file("in_place_script", "# add a line\ngoto 2\ntype two\nenter\n");
+    let mut arguments = vec!["--script".to_string()];
+    arguments.extend(args(&[&script, &file]));
+    Script::from_args(&arguments).unwrap().unwrap().run().unwrap();
+    assert_eq!(fs::read_to_string(&file).unwrap(), "one\ntwo\nthree\n");
+    fs::remove_file(file).unwrap();
+    fs::remove_file(script).unwrap();
+}
+
+#[test]
+fn writes_to_the_output_and_undoes() {
+    let file = temp_file("output", "text\n");
+    let output = temp_file("output_result", "");
+    let script = temp_file("output_script", "end\ntype  more\nctrl+z\ntype !\n");
+    let mut arguments = vec!["--script".to_string()];
+    arguments.extend(args(&[&script]));
+    arguments.push("--output".to_string());
+    arguments.extend(args(&[&output, &file]));
+    Script::from_args(&arguments).unwrap().unwrap().run().unwrap();
+    assert_eq!(fs::read_to_string(&output).unwrap(), "text!\n");
+    assert_eq!(fs::read_to_string(&file).unwrap(), "text\n");
+    fs::remove_file(file).unwrap();
+    fs::remove_file(output).unwrap();
+    fs::remove_file(script).unwrap();
+}
+
+#[test]
+fn refuses_interactive_keys() {
+    let file = temp_file("refuse", "text\n");
+    let script = temp_file("refuse_script", "type x\nctrl+f\n");
+    let mut arguments = vec!["--script".to_string()];
+    arguments.extend(args(&[&script, &file]));
+    let error = Script::from_args(&arguments).unwrap().unwrap().run().unwrap_err();
+    assert!(error.to_string().starts_with("line 2:"));
+    assert_eq!(fs::read_to_string(&file).unwrap(), "text\n");
+    fs::remove_file(file).unwrap();
+    fs::remove_file(script).unwrap();
+}
+
+#[test]
+fn keeps_what_was_saved_before_a_failing_line() {
+    let file = temp_file("save_then_fail", "text\n");
+    let script = temp_file("save_then_fail_script", "type x\nsave\ntype y\nctrl+v\n");
+    let mut arguments = vec!["--script".to_string()];
+    arguments.extend(args(&[&script, &file]));
+    let error = Script::from_args(&arguments).unwrap().unwrap().run().unwrap_err();
+    assert!(error.to_string().starts_with("line 4:"));
+    assert_eq!(fs::read_to_string(&file).unwrap(), "xtext\n");
+    fs::remove_file(file).unwrap();
+    fs::remove_file(script).unwrap();
+}
+
+#[test]
+fn refuses_save_with_an_output() {
+    let file = temp_file("save_output", "text\n");
+    let output = temp_file("save_output_result", "");
+    let script = temp_file("save_output_script", "type x\nsave\n");
+    let mut arguments = vec!["--script".to_string()];
+    arguments.extend(args(&[&script]));
+    arguments.push("--output".to_string());
+    arguments.extend(args(&[&output, &file]));
+    let error = Script::from_args(&arguments).unwrap().unwrap().run().unwrap_err();
+    assert!(error.to_string().starts_with("line 2:"));
+    assert_eq!(fs::read_to_string(&file).unwrap(), "text\n");
+    assert_eq!(fs::read_to_string(&output).unwrap(), "");
+    fs::remove_file(file).unwrap();
+    fs::remove_file(output).unwrap();
+    fs::remove_file(script).unwrap();
+}
+
+#[test]
+fn runs_the_editor_without_a_script() {
+    let arguments = vec!["file.txt".to_string()];
+    assert!(Script::from_args(&arguments).unwrap().is_none());
+}